@@ -0,0 +1,299 @@
+use regex::{Captures, Regex};
+use std::{collections::HashMap, fs, path::Path};
+
+use crate::{
+    discovery::Discovery,
+    error::WrappedError,
+    history::HistoryProvider,
+    parser::{tokenizer::Tokenizer, CommandParser},
+};
+
+lazy_static::lazy_static! {
+    // `name() { body }`, `function name { body }` and fish's `function name`,
+    // the body possibly starting on the next line
+    static ref RE_FUNCTION: Regex = Regex::new(
+        r"^(?:function\s+([\w.:-]+)(?:\s*\(\s*\))?|([\w.:-]+)\s*\(\s*\))\s*\{?\s*(.*)$"
+    )
+    .unwrap();
+    // `$1` and `${1}`
+    static ref RE_POSITIONAL: Regex = Regex::new(r"\$(?:(\d)|\{(\d+)\})").unwrap();
+}
+
+// builtins declaring variables, and fish's `set`
+const DECLARATIONS: [&str; 7] = [
+    "local", "typeset", "declare", "export", "readonly", "integer", "set",
+];
+
+// options that make `abbr` do something else than adding an abbreviation, or
+// add one that is not a plain word
+const ABBR_SKIPPED_OPTIONS: [&str; 14] = [
+    "-e",
+    "--erase",
+    "-l",
+    "--list",
+    "-s",
+    "--show",
+    "-q",
+    "--query",
+    "-r",
+    "--rename",
+    "--regex",
+    "-f",
+    "--function",
+    "--command",
+];
+
+/// Aliases, fish abbreviations and shell functions, with the argument vector
+/// of the first command they run.
+#[derive(Debug, Clone, Default)]
+pub struct Aliases(HashMap<String, Definition>);
+
+#[derive(Debug, Clone)]
+struct Definition {
+    args: Vec<String>,
+    // functions get the arguments of a call as positional parameters, which
+    // aliases append to their expansion
+    function: bool,
+}
+
+impl Aliases {
+    /// Reads the definitions in the rc files of the shells of `providers`.
+    pub fn from_rc_files(providers: &[HistoryProvider]) -> Self {
+        let mut aliases = Aliases::default();
+        for provider in providers {
+            for rc_file in Discovery::alias_files(provider) {
+                if let Ok(content) = fs::read_to_string(rc_file) {
+                    aliases.read(&content, false);
+                }
+            }
+        }
+        aliases
+    }
+
+    /// Reads the output of `alias` or `abbr`, or a script defining aliases.
+    pub fn read_file(&mut self, path: &Path) -> Result<(), WrappedError> {
+        let content = fs::read_to_string(path).map_err(|err| WrappedError::Read {
+            path: path.to_path_buf(),
+            source: err.into(),
+        })?;
+        self.read(&content, true);
+        Ok(())
+    }
+
+    /// Adds an alias of `name` running the command line `expansion`.
+    pub fn insert(&mut self, name: &str, expansion: &str) {
+        self.define(name, expansion, false);
+    }
+
+    /// Adds a shell function of `name` whose body starts with the command
+    /// line `body`.
+    pub fn insert_function(&mut self, name: &str, body: &str) {
+        self.define(name, body, true);
+    }
+
+    /// The argument vector run by `args`, a call of an alias or a function,
+    /// or `None` if `args` starts with neither.
+    pub fn expand(&self, args: &[String]) -> Option<Vec<String>> {
+        let (name, params) = args.split_first()?;
+        let definition = self.0.get(name)?;
+        if !definition.function {
+            return Some([&definition.args, params].concat());
+        }
+        Some(
+            definition
+                .args
+                .iter()
+                .flat_map(|arg| Self::substitute(arg, params))
+                .collect(),
+        )
+    }
+
+    // defines `name` as the first command of `expansion` that is not a
+    // declaration, returning whether there was one
+    fn define(&mut self, name: &str, expansion: &str, function: bool) -> bool {
+        let Some(args) = CommandParser::split(expansion)
+            .iter()
+            .map(|command| Tokenizer::words(command))
+            .find(|args| {
+                args.first()
+                    .is_some_and(|word| !DECLARATIONS.contains(&word.as_str()))
+            })
+        else {
+            return false;
+        };
+        if !name.is_empty() {
+            self.0
+                .insert(name.to_string(), Definition { args, function });
+        }
+        true
+    }
+
+    // replaces the positional parameters of a word of a function body, `$@`,
+    // `$*` and fish's `$argv` standing for all of them. Words left empty are
+    // dropped.
+    fn substitute(word: &str, params: &[String]) -> Vec<String> {
+        if matches!(word, "$@" | "$*" | "$argv") {
+            return params.to_vec();
+        }
+        let substituted = RE_POSITIONAL.replace_all(word, |captures: &Captures| {
+            let n: usize = captures
+                .get(1)
+                .or(captures.get(2))
+                .map_or("", |m| m.as_str())
+                .parse()
+                .unwrap_or_default();
+            n.checked_sub(1)
+                .and_then(|i| params.get(i))
+                .cloned()
+                .unwrap_or_default()
+        });
+        match substituted.is_empty() && !word.is_empty() {
+            true => vec![],
+            false => vec![substituted.into_owned()],
+        }
+    }
+
+    // zsh prints its aliases as bare `name=value` lines, which only count as
+    // aliases in the output of `alias`, not in rc files
+    fn read(&mut self, content: &str, bare: bool) {
+        let mut lines = content.lines().map(str::trim);
+        while let Some(line) = lines.next() {
+            if let Some(abbr) = line.strip_prefix("SETUVAR _fish_abbr_") {
+                if let Some((name, expansion)) = abbr.split_once(':') {
+                    self.insert(name, &Self::unescape_fish(expansion));
+                }
+                continue;
+            }
+            if let Some(captures) = RE_FUNCTION.captures(line) {
+                let name = captures
+                    .get(1)
+                    .or(captures.get(2))
+                    .map_or("", |m| m.as_str());
+                // a body closed on the same line has no more lines to look at
+                let rest = captures[3].trim_end();
+                let closed = rest.ends_with('}') || rest.split_whitespace().last() == Some("end");
+                let mut body = Some(captures[3].trim_end_matches(['}', ';', ' ']));
+                // fish options such as `--wraps` may follow the name, and
+                // declarations such as `local x=1` precede the command run
+                while let Some(line) = body {
+                    if (!line.starts_with('-') && self.define(name, line, true)) || closed {
+                        break;
+                    }
+                    body = lines.next().filter(|line| !matches!(*line, "}" | "end"));
+                }
+                continue;
+            }
+            let words = Tokenizer::words(line);
+            match words.first().map(String::as_str) {
+                Some("alias") => self.read_alias(&words[1..]),
+                Some("abbr") => self.read_abbr(&words[1..]),
+                Some(word) if bare => {
+                    if let Some((name, expansion)) = word.split_once('=') {
+                        self.insert(name, expansion);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    // `alias ll='ls -l' la='ls -a'` in POSIX shells, `alias ll 'ls -l'` in
+    // fish and `alias ll ls -l` in tcsh
+    fn read_alias(&mut self, words: &[String]) {
+        let words: Vec<_> = words
+            .iter()
+            .skip_while(|word| word.starts_with('-'))
+            .collect();
+        match words.first().map(|word| word.split_once('=')) {
+            Some(Some(_)) => {
+                for (name, expansion) in words.iter().filter_map(|word| word.split_once('=')) {
+                    self.insert(name, expansion);
+                }
+            }
+            Some(None) if words.len() > 1 => {
+                let expansion: Vec<_> = words[1..].iter().map(|word| word.as_str()).collect();
+                self.insert(words[0], &expansion.join(" "));
+            }
+            _ => {}
+        }
+    }
+
+    // `abbr -a gco git checkout`, and `abbr -a -- gco 'git checkout'` as
+    // printed by `abbr`
+    fn read_abbr(&mut self, words: &[String]) {
+        if words
+            .iter()
+            .any(|word| ABBR_SKIPPED_OPTIONS.contains(&word.as_str()))
+        {
+            return;
+        }
+        let mut rest = vec![];
+        let mut iter = words.iter();
+        while let Some(word) = iter.next() {
+            match word.as_str() {
+                "-p" | "--position" => {
+                    iter.next();
+                }
+                "--" => rest.extend(iter.by_ref()),
+                word if word.starts_with('-') => {}
+                _ => rest.push(word),
+            }
+        }
+        if let [name, expansion @ ..] = rest.as_slice() {
+            let expansion: Vec<_> = expansion.iter().map(|word| word.as_str()).collect();
+            self.insert(name, &expansion.join(" "));
+        }
+    }
+
+    // fish_variables escapes spaces and other bytes as `\xHH`
+    fn unescape_fish(value: &str) -> String {
+        let mut bytes = vec![];
+        let mut rest = value.as_bytes();
+        while let Some((&b, tail)) = rest.split_first() {
+            match (b, tail) {
+                (b'\\', [b'x', hi, lo, tail @ ..]) => {
+                    match u8::from_str_radix(&format!("{}{}", *hi as char, *lo as char), 16) {
+                        Ok(b) => bytes.push(b),
+                        Err(_) => bytes.extend_from_slice(&[b'\\', b'x', *hi, *lo]),
+                    }
+                    rest = tail;
+                }
+                (b'\\', [escaped, tail @ ..]) => {
+                    bytes.push(*escaped);
+                    rest = tail;
+                }
+                _ => {
+                    bytes.push(b);
+                    rest = tail;
+                }
+            }
+        }
+        String::from_utf8_lossy(&bytes).into_owned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn expand(aliases: &Aliases, line: &str) -> Option<Vec<String>> {
+        aliases.expand(&Tokenizer::words(line))
+    }
+
+    #[test]
+    fn declarations_are_skipped() {
+        let mut aliases = Aliases::default();
+        aliases.read(
+            "foo() {\n  local x=1\n  typeset -a y\n  ls \"$@\"\n}\n\
+             bar() { export A=1; make \"$1\"; }\n\
+             baz() { local x; }\nalias ll='ls -l'\n\
+             function qux --wraps git\n  set -l x 1\n  git $argv\nend\n",
+            false,
+        );
+        assert_eq!(expand(&aliases, "foo -a").unwrap(), ["ls", "-a"]);
+        assert_eq!(expand(&aliases, "bar all").unwrap(), ["make", "all"]);
+        assert_eq!(expand(&aliases, "baz"), None);
+        assert_eq!(expand(&aliases, "ll").unwrap(), ["ls", "-l"]);
+        assert_eq!(expand(&aliases, "qux status").unwrap(), ["git", "status"]);
+    }
+}
@@ -0,0 +1,78 @@
+use flate2::read::MultiGzDecoder;
+use std::{
+    error::Error,
+    fs,
+    io::Read,
+    path::{Path, PathBuf},
+};
+use xz2::read::XzDecoder;
+
+use crate::error::WrappedError;
+
+const GZIP_MAGIC: &[u8] = b"\x1f\x8b";
+const ZSTD_MAGIC: &[u8] = b"\x28\xb5\x2f\xfd";
+const XZ_MAGIC: &[u8] = b"\xfd7zXZ\x00";
+
+/// Compression of a rotated history file, recognized by its magic bytes
+/// rather than by its extension.
+#[derive(Debug, Clone, Copy)]
+pub enum Compression {
+    Gzip,
+    Zstd,
+    Xz,
+}
+
+impl Compression {
+    pub fn detect(header: &[u8]) -> Option<Self> {
+        if header.starts_with(GZIP_MAGIC) {
+            Some(Compression::Gzip)
+        } else if header.starts_with(ZSTD_MAGIC) {
+            Some(Compression::Zstd)
+        } else if header.starts_with(XZ_MAGIC) {
+            Some(Compression::Xz)
+        } else {
+            None
+        }
+    }
+
+    pub fn decode(self, reader: Box<dyn Read>) -> Result<Box<dyn Read>, Box<dyn Error>> {
+        Ok(match self {
+            Compression::Gzip => Box::new(MultiGzDecoder::new(reader)),
+            Compression::Zstd => Box::new(zstd::Decoder::new(reader)?),
+            Compression::Xz => Box::new(XzDecoder::new_multi_decoder(reader)),
+        })
+    }
+}
+
+pub struct Archive;
+
+impl Archive {
+    /// Expands a glob pattern such as `~/.zsh_history*` into the files it
+    /// matches, oldest first. Paths without wildcards are returned as is.
+    pub fn expand(pattern: &Path) -> Result<Vec<PathBuf>, WrappedError> {
+        let pattern_str = pattern.to_string_lossy();
+        if pattern.exists() || !pattern_str.contains(['*', '?', '[']) {
+            return Ok(vec![pattern.to_path_buf()]);
+        }
+        let mut paths = glob::glob(&pattern_str)
+            .map_err(|err| WrappedError::Read {
+                path: pattern.to_path_buf(),
+                source: err.into(),
+            })?
+            .filter_map(Result::ok)
+            .filter(|path| path.is_file())
+            .collect::<Vec<_>>();
+        if paths.is_empty() {
+            Err(WrappedError::NoMatchingFile(pattern.to_path_buf()))?
+        }
+        // rotated files keep their modification time, while their names
+        // sort differently depending on the rotation scheme
+        paths.sort_by_key(|path| {
+            (
+                fs::metadata(path).and_then(|m| m.modified()).ok(),
+                path.clone(),
+            )
+        });
+        Ok(paths)
+    }
+}
@@ -1,17 +1,34 @@
-use clap::{arg, command, value_parser, Arg};
-use std::env;
+use clap::{arg, command, value_parser, Arg, ArgAction};
+use std::{
+    env,
+    io::{stdin, BufRead},
+    path::PathBuf,
+};
 
 pub struct Cli {
     pub year: i32,
-    pub shell: String,
+    pub shells: Vec<String>,
+    pub history_files: Vec<PathBuf>,
+    pub format: Option<String>,
+    pub infer_time: bool,
+    pub plot_estimated: bool,
+    pub diagnose: bool,
+    pub config: Option<PathBuf>,
+    pub alias_files: Vec<PathBuf>,
+    pub no_aliases: bool,
 }
 
 impl Cli {
+    // how long a pipe may take to deliver its first bytes, as the writer of
+    // `history | cmd-wrapped` starts at the same time
+    #[cfg(unix)]
+    const STDIN_WAIT_MS: i32 = 500;
+
     fn current_shell() -> String {
         env::var("SHELL")
             .unwrap_or("unknown".into())
             .split('/')
-            .last()
+            .next_back()
             .unwrap_or("unknown")
             .into()
     }
@@ -26,21 +43,179 @@ impl Cli {
             )
             .arg(
                 arg!(
-                -s --shell <SHELL> "Specify the target shell / history tool.\nSupported options - zsh, bash, fish, atuin"
+                -s --shell <SHELL> "Specify the target shells / history tools, separated by commas.\nSupported options - zsh, bash, fish, atuin, nu, pwsh, xonsh, tcsh, ksh, histdb, mcfly, hishtory,\nor a provider declared in the config file"
+                )
+                .required(false)
+                .value_delimiter(','),
+            )
+            .arg(
+                arg!(
+                -f --"history-file" <PATH> "Read history from the specified file instead of the shell default, can be repeated.\nUse `-` for stdin, which is also read when piped without --shell.\nEach file is parsed as --format, or as the shell at the same position in --shell,\nor detected automatically"
+                )
+                .required(false)
+                .action(ArgAction::Append)
+                .value_parser(value_parser!(PathBuf)),
+            )
+            .arg(
+                arg!(
+                --format <FORMAT> "Specify the format of the history files.\nSupported options - auto, zsh, bash, fish, atuin, nu, pwsh, xonsh, tcsh, ksh, histdb, mcfly, hishtory,\nor a provider declared in the config file"
                 )
                 .required(false),
             )
+            .arg(
+                arg!(
+                --"infer-time" "Estimate timestamps of untimed commands from the timed ones around them"
+                )
+                .action(ArgAction::SetTrue),
+            )
+            .arg(
+                arg!(
+                --"plot-estimated" "Include commands with estimated timestamps in the daily and hourly graphs"
+                )
+                .requires("infer-time")
+                .action(ArgAction::SetTrue),
+            )
+            .arg(
+                arg!(
+                --config <PATH> "Read custom history providers from the specified file\n[default: ~/.config/cmd-wrapped/config.toml]"
+                )
+                .required(false)
+                .value_parser(value_parser!(PathBuf)),
+            )
+            .arg(
+                arg!(
+                --aliases <PATH> "Expand the aliases listed in the specified file, such as the output of `alias` or `abbr`,\nin addition to those of the rc files, can be repeated"
+                )
+                .required(false)
+                .action(ArgAction::Append)
+                .value_parser(value_parser!(PathBuf)),
+            )
+            .arg(
+                arg!(
+                --"no-aliases" "Count aliases and shell functions as typed, without expanding them"
+                )
+                .conflicts_with("aliases")
+                .action(ArgAction::SetTrue),
+            )
+            .arg(
+                arg!(
+                --diagnose "Report the history entries that could not be parsed instead of the stats"
+                )
+                .action(ArgAction::SetTrue),
+            )
             .get_matches();
 
         let year = args
             .get_one::<i32>("year")
             .map(|ptr| ptr.to_owned())
             .unwrap_or_default();
-        let shell = args
-            .get_one::<String>("shell")
-            .map(|ptr| ptr.to_owned())
-            .unwrap_or(Self::current_shell());
+        let shells = args
+            .get_many::<String>("shell")
+            .map(|shells| shells.cloned().collect())
+            .unwrap_or_default();
+        let history_files = args
+            .get_many::<PathBuf>("history-file")
+            .map(|files| files.cloned().collect())
+            .unwrap_or_default();
+        let format = args.get_one::<String>("format").cloned();
+        let infer_time = args.get_flag("infer-time");
+        let plot_estimated = args.get_flag("plot-estimated");
+        let diagnose = args.get_flag("diagnose");
+        let config = args.get_one::<PathBuf>("config").cloned();
+        let alias_files = args
+            .get_many::<PathBuf>("aliases")
+            .map(|files| files.cloned().collect())
+            .unwrap_or_default();
+        let no_aliases = args.get_flag("no-aliases");
+
+        Cli {
+            year,
+            shells,
+            history_files,
+            format,
+            infer_time,
+            plot_estimated,
+            diagnose,
+            config,
+            alias_files,
+            no_aliases,
+        }
+    }
+
+    /// Pairs every history file with the format to parse it in, or every
+    /// shell with its default history file when no file is given.
+    pub fn sources(&self) -> Vec<(String, Option<PathBuf>)> {
+        let mut history_files = self.history_files.clone();
+        if history_files.is_empty() && self.shells.is_empty() && Self::piped_stdin() {
+            history_files.push("-".into());
+        }
+        if history_files.is_empty() {
+            let shells = match self.shells.is_empty() {
+                true => vec![Self::current_shell()],
+                false => self.shells.clone(),
+            };
+            return shells
+                .into_iter()
+                .map(|shell| (self.format.clone().unwrap_or(shell), None))
+                .collect();
+        }
+        history_files
+            .into_iter()
+            .enumerate()
+            .map(|(i, file)| {
+                let format = self
+                    .format
+                    .as_ref()
+                    .or(self.shells.get(i))
+                    .or(self.shells.first())
+                    .map_or("auto", |format| format.as_str());
+                (format.into(), Some(file))
+            })
+            .collect()
+    }
+
+    // stdin is read as a history when a pipe or a file has data on it, not
+    // when it is empty or idle as in cron jobs, CI or `ssh host cmd-wrapped`.
+    // The peeked bytes stay in its buffer.
+    #[cfg(unix)]
+    fn piped_stdin() -> bool {
+        use std::{
+            fs::File,
+            os::{
+                fd::{AsFd, AsRawFd},
+                unix::fs::FileTypeExt,
+            },
+        };
+
+        let stdin = stdin();
+        let Ok(file_type) = stdin
+            .as_fd()
+            .try_clone_to_owned()
+            .and_then(|fd| File::from(fd).metadata())
+            .map(|metadata| metadata.file_type())
+        else {
+            return false;
+        };
+        if file_type.is_fifo() {
+            let mut pollfd = libc::pollfd {
+                fd: stdin.as_raw_fd(),
+                events: libc::POLLIN,
+                revents: 0,
+            };
+            // SAFETY: `pollfd` is a valid array of one element for the call
+            let ready = unsafe { libc::poll(&mut pollfd, 1, Self::STDIN_WAIT_MS) };
+            if ready != 1 {
+                return false;
+            }
+        } else if !file_type.is_file() {
+            return false;
+        }
+        stdin.lock().fill_buf().is_ok_and(|buf| !buf.is_empty())
+    }
 
-        Cli { year, shell }
+    // stdin is only read with `-f -`
+    #[cfg(not(unix))]
+    fn piped_stdin() -> bool {
+        false
     }
 }
@@ -0,0 +1,156 @@
+use regex::Regex;
+use serde::Deserialize;
+use std::{
+    collections::HashMap,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    discovery::Discovery,
+    error::WrappedError,
+    subcommand::{Tool, Tools},
+    wrapper::{Wrapper, Wrappers},
+};
+
+/// A history format declared in the config file, for logs that are not
+/// written by a supported shell:
+///
+/// ```toml
+/// [providers.psql]
+/// file = "~/.psql_audit.log"
+/// delimiter = "\n"
+/// pattern = '^\[(?P<time>[^\]]+)\] (?P<command>.+)$'
+/// time_format = "%Y-%m-%d %H:%M:%S"
+/// ```
+///
+/// `pattern` needs a `command` capture. `time`, `exit`, `cwd`, `host` and
+/// `session` captures are read when present, `time` in the chrono
+/// `time_format`, Unix timestamps (`%s`) by default. Records are lines unless
+/// another `delimiter` is given.
+#[derive(Debug, Clone)]
+pub struct PatternProvider {
+    pub name: String,
+    pub file: Option<PathBuf>,
+    pub delimiter: String,
+    pub pattern: Regex,
+    pub time_format: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PatternProviderConfig {
+    file: Option<String>,
+    #[serde(default = "PatternProviderConfig::default_delimiter")]
+    delimiter: String,
+    pattern: String,
+    time_format: Option<String>,
+}
+
+impl PatternProviderConfig {
+    fn default_delimiter() -> String {
+        "\n".into()
+    }
+}
+
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+struct ConfigFile {
+    #[serde(default)]
+    providers: HashMap<String, PatternProviderConfig>,
+    #[serde(default)]
+    wrappers: HashMap<String, Wrapper>,
+    #[serde(default)]
+    tools: HashMap<String, Tool>,
+}
+
+#[derive(Default)]
+pub struct Config {
+    providers: HashMap<String, PatternProvider>,
+    wrappers: Wrappers,
+    tools: Tools,
+}
+
+impl Config {
+    /// `$XDG_CONFIG_HOME/cmd-wrapped/config.toml`.
+    pub fn default_path() -> PathBuf {
+        Discovery::config_dir().join("cmd-wrapped/config.toml")
+    }
+
+    /// Loads the config file at `path`, or the default one. A missing default
+    /// config file is an empty config.
+    pub fn load(path: Option<&Path>) -> Result<Self, WrappedError> {
+        let (path, required) = match path {
+            Some(path) => (path.to_path_buf(), true),
+            None => (Self::default_path(), false),
+        };
+        let content = match fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(err) if err.kind() == io::ErrorKind::NotFound && !required => {
+                return Ok(Config::default())
+            }
+            Err(err) => Err(WrappedError::Read {
+                path: path.clone(),
+                source: err.into(),
+            })?,
+        };
+        let config_error = |message: String| WrappedError::Config(path.clone(), message);
+        let config: ConfigFile =
+            toml::from_str(&content).map_err(|err| config_error(err.message().into()))?;
+
+        let mut providers = HashMap::new();
+        for (name, provider) in config.providers {
+            let pattern = Regex::new(&provider.pattern)
+                .map_err(|err| config_error(format!("invalid pattern of {}: {}", name, err)))?;
+            if !pattern
+                .capture_names()
+                .any(|capture| capture == Some("command"))
+            {
+                Err(config_error(format!(
+                    "the pattern of {} has no `command` capture",
+                    name
+                )))?
+            }
+            if provider.delimiter.is_empty() {
+                Err(config_error(format!("the delimiter of {} is empty", name)))?
+            }
+            providers.insert(
+                name.clone(),
+                PatternProvider {
+                    name,
+                    file: provider.file.as_deref().and_then(Discovery::expand),
+                    delimiter: provider.delimiter,
+                    pattern,
+                    time_format: provider.time_format,
+                },
+            );
+        }
+        let mut wrappers = Wrappers::default();
+        for (name, wrapper) in config.wrappers {
+            wrappers.insert(name, wrapper);
+        }
+        let mut tools = Tools::default();
+        for (name, tool) in config.tools {
+            tools.insert(name, tool);
+        }
+        Ok(Config {
+            providers,
+            wrappers,
+            tools,
+        })
+    }
+
+    pub fn provider(&self, name: &str) -> Option<&PatternProvider> {
+        self.providers.get(name)
+    }
+
+    /// The built-in wrappers, with those of the config file.
+    pub fn wrappers(&self) -> &Wrappers {
+        &self.wrappers
+    }
+
+    /// The built-in tools with subcommands, with those of the config file.
+    pub fn tools(&self) -> &Tools {
+        &self.tools
+    }
+}
@@ -0,0 +1,180 @@
+use regex::Regex;
+use rusqlite::{Connection, OpenFlags};
+use std::{error::Error, path::Path};
+
+use crate::{history::HistoryProvider, reader::ksh::KshReader};
+
+lazy_static::lazy_static! {
+    static ref RE_ZSH_EXTENDED: Regex = Regex::new(r"^: \d+:\d+;").unwrap();
+    static ref RE_BASH_TIMESTAMP: Regex = Regex::new(r"^#\d+$").unwrap();
+    static ref RE_TCSH_TIMESTAMP: Regex = Regex::new(r"^#\+\d+$").unwrap();
+    static ref RE_ATUIN_LIST: Regex = Regex::new(r"^\d{4}-\d{2}-\d{2} \d{2}:\d{2}:\d{2};").unwrap();
+    static ref RE_FISH_SHOW_TIME: Regex = Regex::new(r"^\d+;").unwrap();
+}
+
+pub const SQLITE_MAGIC: &[u8] = b"SQLite format 3\0";
+
+/// Guesses the format of a history file from its first bytes.
+pub struct Detector;
+
+impl Detector {
+    const SNIFF_LINES: usize = 50;
+
+    /// Detects text and binary formats from the start of the file. Plain
+    /// zsh history is told from plain bash history by its metafied bytes or
+    /// multi-line commands, and is read as bash otherwise.
+    pub fn detect(header: &[u8]) -> Option<HistoryProvider> {
+        if header.starts_with(b"- cmd:") {
+            return Some(HistoryProvider::Fish);
+        }
+        if KshReader::is_binary(header) {
+            return Some(HistoryProvider::Ksh);
+        }
+        if header.trim_ascii_start().starts_with(b"{") {
+            return Some(HistoryProvider::Xonsh);
+        }
+
+        let metafied = Self::is_metafied(header);
+        let header = String::from_utf8_lossy(header);
+        let lines: Vec<_> = header
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .take(Self::SNIFF_LINES)
+            .collect();
+        if lines.is_empty() {
+            return None;
+        }
+        let any = |re: &Regex| lines.iter().any(|line| re.is_match(line));
+        Some(if any(&RE_ZSH_EXTENDED) {
+            HistoryProvider::Zsh
+        } else if any(&RE_TCSH_TIMESTAMP) {
+            HistoryProvider::Tcsh
+        } else if any(&RE_BASH_TIMESTAMP) {
+            HistoryProvider::Bash
+        } else if any(&RE_ATUIN_LIST) {
+            HistoryProvider::Atuin
+        } else if lines
+            .iter()
+            .filter(|line| RE_FISH_SHOW_TIME.is_match(line))
+            .count()
+            * 2
+            > lines.len()
+        {
+            HistoryProvider::Fish
+        } else if metafied || lines.iter().any(|line| line.ends_with('\\')) {
+            // zsh ends every line but the last of a multi-line command with a
+            // backslash, where bash drops it
+            HistoryProvider::Zsh
+        } else {
+            HistoryProvider::Bash
+        })
+    }
+
+    // zsh escapes the bytes 0x83 to 0xa2 of non-ASCII text with a 0x83 meta
+    // byte, which breaks the UTF-8 encoding. The header may end in the middle
+    // of a character.
+    fn is_metafied(header: &[u8]) -> bool {
+        header.contains(&0x83)
+            && std::str::from_utf8(header).is_err_and(|err| err.error_len().is_some())
+    }
+
+    /// Tells the SQLite history databases apart by their tables.
+    pub fn detect_database(path: &Path) -> Result<HistoryProvider, Box<dyn Error>> {
+        let conn = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+        let has_column = |table: &str, column: &str| -> Result<bool, rusqlite::Error> {
+            conn.prepare(&format!(
+                "SELECT * FROM pragma_table_info('{}') WHERE name = ?",
+                table
+            ))?
+            .exists([column])
+        };
+        if has_column("history_entries", "command")? {
+            Ok(HistoryProvider::Hishtory)
+        } else if has_column("places", "host")? {
+            Ok(HistoryProvider::Histdb)
+        } else if has_column("commands", "when_run")? {
+            Ok(HistoryProvider::McFly)
+        } else if has_column("history", "command_line")? {
+            Ok(HistoryProvider::Nu)
+        } else if has_column("history", "command")? {
+            Ok(HistoryProvider::Atuin)
+        } else {
+            Err(format!("unknown history database: {}", path.display()))?
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn detect(header: &str) -> Option<HistoryProvider> {
+        Detector::detect(header.as_bytes())
+    }
+
+    #[test]
+    fn text_formats() {
+        assert_eq!(
+            detect(": 1700000000:0;ls\n: 1700000005:2;make\n"),
+            Some(HistoryProvider::Zsh)
+        );
+        assert_eq!(
+            detect("#1700000000\nls\n#1700000005\nmake\n"),
+            Some(HistoryProvider::Bash)
+        );
+        assert_eq!(
+            detect("#+1700000000\nls\n#+1700000005\nmake\n"),
+            Some(HistoryProvider::Tcsh)
+        );
+        assert_eq!(
+            detect("2023-11-14 22:13:20;ls\n2023-11-14 22:13:25;make\n"),
+            Some(HistoryProvider::Atuin)
+        );
+        assert_eq!(
+            detect("1700000000;ls\n1700000005;make\n"),
+            Some(HistoryProvider::Fish)
+        );
+        assert_eq!(
+            detect("- cmd: ls\n  when: 1700000000\n"),
+            Some(HistoryProvider::Fish)
+        );
+        assert_eq!(
+            detect("{\"data\": {\"cmds\": []}}"),
+            Some(HistoryProvider::Xonsh)
+        );
+        assert_eq!(detect("ls\nmake\n"), Some(HistoryProvider::Bash));
+        assert_eq!(detect("\n\n"), None);
+    }
+
+    #[test]
+    fn sniffing_order() {
+        // a zsh entry whose command looks like a bash timestamp
+        assert_eq!(
+            detect(": 1700000000:0;ls\n#1700000005\n"),
+            Some(HistoryProvider::Zsh)
+        );
+        // tcsh timestamps are not read as bash comments
+        assert_eq!(
+            detect("#+1700000000\nls\n#1700000005\n"),
+            Some(HistoryProvider::Tcsh)
+        );
+        // a few `n;` lines in a plain history are not fish's `%s;` output
+        assert_eq!(detect("ls\nmake\n1;\npwd\n"), Some(HistoryProvider::Bash));
+    }
+
+    #[test]
+    fn plain_zsh() {
+        let multi_line = b"ls\nfor x in a b\\\ndo echo $x\\\ndone\n";
+        assert_eq!(Detector::detect(multi_line), Some(HistoryProvider::Zsh));
+        // `echo é`, the 0xa9 of `é` being metafied
+        let metafied = b"ls\necho \xc3\x83\x89\n";
+        assert_eq!(Detector::detect(metafied), Some(HistoryProvider::Zsh));
+        let utf8 = "ls\necho ă ヂ\n".as_bytes();
+        assert_eq!(Detector::detect(utf8), Some(HistoryProvider::Bash));
+        // a header ending in the middle of `ヂ`
+        assert_eq!(
+            Detector::detect(&utf8[..utf8.len() - 2]),
+            Some(HistoryProvider::Bash)
+        );
+    }
+}
@@ -0,0 +1,272 @@
+use regex::Regex;
+use std::{env, fs, path::PathBuf, str::FromStr};
+
+use crate::history::HistoryProvider;
+
+lazy_static::lazy_static! {
+    static ref RE_HISTFILE: Regex = Regex::new(
+        r#"^\s*(?:export\s+|(?:typeset|declare|local)\s+(?:-\w+\s+)*)?HISTFILE=((?:"[^"]*"|'[^']*'|[^\s;"'])+)\s*(?:[;#].*)?$"#
+    )
+    .unwrap();
+    static ref RE_TCSH_HISTFILE: Regex = Regex::new(
+        r#"^\s*set\s+histfile\s*=\s*((?:"[^"]*"|'[^']*'|[^\s;"'])+)\s*(?:[;#].*)?$"#
+    )
+    .unwrap();
+    static ref RE_VARIABLE: Regex = Regex::new(r"\$\{?(\w+)(?::-([^}]*))?\}?").unwrap();
+}
+
+/// Locates history files without starting an interactive shell.
+pub struct Discovery;
+
+impl Discovery {
+    /// Resolves the history file of `provider`, trying `$HISTFILE`, `HISTFILE=`
+    /// assignments in the rc files and finally the shell defaults.
+    pub fn history_file(provider: &HistoryProvider) -> Option<PathBuf> {
+        Self::from_env(provider)
+            .into_iter()
+            .chain(Self::from_rc_files(provider))
+            .chain(Self::defaults(provider))
+            .find(|path| path.exists())
+    }
+
+    fn home() -> PathBuf {
+        env::var_os("HOME").map(PathBuf::from).unwrap_or_default()
+    }
+
+    fn zdotdir() -> PathBuf {
+        env::var_os("ZDOTDIR")
+            .map(PathBuf::from)
+            .unwrap_or_else(Self::home)
+    }
+
+    /// `$XDG_DATA_HOME`, falling back to `~/.local/share`.
+    pub fn data_dir() -> PathBuf {
+        env::var_os("XDG_DATA_HOME")
+            .map(PathBuf::from)
+            .filter(|path| path.is_absolute())
+            .unwrap_or_else(|| Self::home().join(".local/share"))
+    }
+
+    /// `$XDG_CONFIG_HOME`, falling back to `~/.config`.
+    pub fn config_dir() -> PathBuf {
+        env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .filter(|path| path.is_absolute())
+            .unwrap_or_else(|| Self::home().join(".config"))
+    }
+
+    fn current_shell() -> Option<String> {
+        let shell = env::var("SHELL").ok()?;
+        Some(shell.rsplit('/').next()?.to_string())
+    }
+
+    // $HISTFILE is usually a shell variable; when it is exported, it only
+    // describes the shell we were started from
+    fn from_env(provider: &HistoryProvider) -> Option<PathBuf> {
+        if HistoryProvider::from_str(&Self::current_shell()?).ok()? != *provider {
+            return None;
+        }
+        let histfile = env::var("HISTFILE").ok()?;
+        Self::expand(&histfile)
+    }
+
+    fn rc_files(provider: &HistoryProvider) -> Vec<PathBuf> {
+        match provider {
+            HistoryProvider::Zsh => [".zshenv", ".zprofile", ".zshrc"]
+                .iter()
+                .map(|file| Self::zdotdir().join(file))
+                .collect(),
+            HistoryProvider::Bash => [".profile", ".bash_profile", ".bashrc"]
+                .iter()
+                .map(|file| Self::home().join(file))
+                .collect(),
+            HistoryProvider::Ksh => [".profile", ".kshrc", ".mkshrc"]
+                .iter()
+                .map(|file| Self::home().join(file))
+                .collect(),
+            HistoryProvider::Tcsh => [".cshrc", ".tcshrc"]
+                .iter()
+                .map(|file| Self::home().join(file))
+                .collect(),
+            _ => vec![],
+        }
+    }
+
+    /// The files that may define aliases, abbreviations and functions for the
+    /// shell of `provider`, in the order they are sourced.
+    pub fn alias_files(provider: &HistoryProvider) -> Vec<PathBuf> {
+        let mut files = Self::rc_files(provider);
+        match provider {
+            HistoryProvider::Zsh => files.push(Self::zdotdir().join(".zsh_aliases")),
+            HistoryProvider::Bash => files.push(Self::home().join(".bash_aliases")),
+            HistoryProvider::Fish => {
+                let fish_dir = Self::config_dir().join("fish");
+                files.push(fish_dir.join("config.fish"));
+                let mut conf_d: Vec<_> = fs::read_dir(fish_dir.join("conf.d"))
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|entry| Some(entry.ok()?.path()))
+                    .filter(|path| path.extension().is_some_and(|ext| ext == "fish"))
+                    .collect();
+                conf_d.sort();
+                files.extend(conf_d);
+                files.push(fish_dir.join("fish_variables"));
+            }
+            _ => {}
+        }
+        files
+    }
+
+    // rc files are sourced in order, so the last assignment wins
+    fn from_rc_files(provider: &HistoryProvider) -> Option<PathBuf> {
+        let re: &Regex = match provider {
+            HistoryProvider::Tcsh => &RE_TCSH_HISTFILE,
+            _ => &RE_HISTFILE,
+        };
+        Self::rc_files(provider)
+            .iter()
+            .filter_map(|rc_file| fs::read_to_string(rc_file).ok())
+            .flat_map(|content| {
+                content
+                    .lines()
+                    .filter_map(|line| re.captures(line))
+                    .filter_map(|captures| Self::expand(captures.get(1)?.as_str()))
+                    .collect::<Vec<_>>()
+            })
+            .last()
+    }
+
+    fn defaults(provider: &HistoryProvider) -> Vec<PathBuf> {
+        match provider {
+            HistoryProvider::Zsh => vec![
+                Self::zdotdir().join(".zsh_history"),
+                Self::zdotdir().join(".histfile"),
+            ],
+            HistoryProvider::Bash => vec![Self::home().join(".bash_history")],
+            HistoryProvider::Atuin => vec![Self::data_dir().join("atuin/history.db")],
+            HistoryProvider::Fish => {
+                // `$fish_history` selects the session, an empty one disables history
+                let session = env::var("fish_history").unwrap_or("fish".into());
+                vec![Self::data_dir().join(format!("fish/{}_history", session))]
+            }
+            HistoryProvider::Nu => [
+                Self::config_dir().join("nushell"),
+                Self::home().join("Library/Application Support/nushell"),
+            ]
+            .iter()
+            .flat_map(|dir| [dir.join("history.sqlite3"), dir.join("history.txt")])
+            .collect(),
+            HistoryProvider::Tcsh => vec![Self::home().join(".history")],
+            HistoryProvider::Ksh => vec![
+                Self::home().join(".mksh_history"),
+                Self::home().join(".sh_history"),
+            ],
+            HistoryProvider::Histdb => {
+                let histdb_file = env::var_os("HISTDB_FILE")
+                    .map(PathBuf::from)
+                    .unwrap_or_else(|| Self::home().join(".histdb/zsh-history.db"));
+                vec![histdb_file]
+            }
+            HistoryProvider::McFly => vec![
+                Self::data_dir().join("mcfly/history.db"),
+                Self::home().join(".mcfly/history.db"),
+                Self::home().join("Library/Application Support/McFly/history.db"),
+            ],
+            HistoryProvider::Hishtory => {
+                let hishtory_dir = env::var("HISHTORY_PATH").unwrap_or(".hishtory".into());
+                vec![Self::home().join(hishtory_dir).join(".hishtory.db")]
+            }
+            HistoryProvider::Xonsh => {
+                let data_dir = env::var_os("XONSH_DATA_DIR")
+                    .map(PathBuf::from)
+                    .unwrap_or_else(|| Self::data_dir().join("xonsh"));
+                vec![data_dir.join("history_json")]
+            }
+            HistoryProvider::Pwsh => {
+                vec![Self::data_dir().join("powershell/PSReadLine/ConsoleHost_history.txt")]
+            }
+        }
+    }
+
+    /// Expands quotes, `~` and the variables commonly used in `HISTFILE`.
+    /// Returns `None` for values referencing anything unknown.
+    pub fn expand(value: &str) -> Option<PathBuf> {
+        let value = value.trim().trim_matches(|c| c == '"' || c == '\'');
+        let value = match value.strip_prefix('~') {
+            Some(rest) => Self::home().to_string_lossy().into_owned() + rest,
+            None => value.to_string(),
+        };
+        let expanded = Self::expand_variables(&value)?;
+        if expanded.is_empty() {
+            return None;
+        }
+        Some(PathBuf::from(expanded))
+    }
+
+    fn expand_variables(value: &str) -> Option<String> {
+        let mut res = String::new();
+        let mut last = 0;
+        for captures in RE_VARIABLE.captures_iter(value) {
+            let range = captures.get(0)?.range();
+            let variable = match &captures[1] {
+                "HOME" => Self::home().to_string_lossy().into_owned(),
+                "ZDOTDIR" => Self::zdotdir().to_string_lossy().into_owned(),
+                name => match (env::var(name), captures.get(2)) {
+                    (Ok(variable), _) => variable,
+                    (Err(_), Some(default)) => Self::expand_variables(default.as_str())?,
+                    (Err(_), None) => return None,
+                },
+            };
+            res += &value[last..range.start];
+            res += &variable;
+            last = range.end;
+        }
+        res += &value[last..];
+        Some(res)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn histfile(re: &Regex, line: &str) -> Option<String> {
+        Some(re.captures(line)?[1].to_string())
+    }
+
+    #[test]
+    fn histfile_assignments() {
+        let histfile = |line| histfile(&RE_HISTFILE, line);
+        assert_eq!(
+            histfile("HISTFILE=~/.zsh_history").unwrap(),
+            "~/.zsh_history"
+        );
+        assert_eq!(
+            histfile("export HISTFILE=~/.zsh_history # comment").unwrap(),
+            "~/.zsh_history"
+        );
+        assert_eq!(
+            histfile("typeset -g HISTFILE=$HOME/.hist; setopt share_history").unwrap(),
+            "$HOME/.hist"
+        );
+        assert_eq!(
+            histfile(r#"HISTFILE="$HOME/my #1;history" # comment"#).unwrap(),
+            r#""$HOME/my #1;history""#
+        );
+        assert_eq!(histfile("HISTFILE=~/.hist#1").unwrap(), "~/.hist#1");
+        assert_eq!(histfile("# HISTFILE=~/.zsh_history"), None);
+    }
+
+    #[test]
+    fn tcsh_histfile_assignments() {
+        let histfile = |line| histfile(&RE_TCSH_HISTFILE, line);
+        assert_eq!(
+            histfile("set histfile = ~/.tcsh_history # comment").unwrap(),
+            "~/.tcsh_history"
+        );
+        assert_eq!(
+            histfile("set histfile='~/.history'").unwrap(),
+            "'~/.history'"
+        );
+    }
+}
@@ -0,0 +1,176 @@
+use std::{collections::BTreeMap, error::Error, fmt, io, path::PathBuf};
+
+const EX_USAGE: i32 = 64;
+const EX_DATAERR: i32 = 65;
+const EX_NOINPUT: i32 = 66;
+const EX_IOERR: i32 = 74;
+const EX_CONFIG: i32 = 78;
+
+/// Errors that stop cmd-wrapped from producing a report.
+#[derive(Debug)]
+pub enum WrappedError {
+    UnsupportedShell(String),
+    HistoryNotFound(String),
+    NoMatchingFile(PathBuf),
+    DetectionNeedsFile,
+    UnknownFormat(PathBuf),
+    CompressedDatabase(PathBuf),
+    DatabaseFromStdin,
+    Read {
+        path: PathBuf,
+        source: Box<dyn Error>,
+    },
+    Io(io::Error),
+    Config(PathBuf, String),
+}
+
+impl WrappedError {
+    /// Exit codes follow sysexits(3).
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            WrappedError::UnsupportedShell(_)
+            | WrappedError::DetectionNeedsFile
+            | WrappedError::DatabaseFromStdin => EX_USAGE,
+            WrappedError::HistoryNotFound(_) | WrappedError::NoMatchingFile(_) => EX_NOINPUT,
+            WrappedError::UnknownFormat(_) | WrappedError::CompressedDatabase(_) => EX_DATAERR,
+            WrappedError::Read { .. } | WrappedError::Io(_) => EX_IOERR,
+            WrappedError::Config(..) => EX_CONFIG,
+        }
+    }
+}
+
+impl fmt::Display for WrappedError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WrappedError::UnsupportedShell(shell) => write!(
+                f,
+                "sorry, {} is not supported yet, see --help for the supported shells",
+                shell
+            ),
+            WrappedError::HistoryNotFound(provider) => write!(
+                f,
+                "failed to locate the {} history file, try --history-file",
+                provider
+            ),
+            WrappedError::NoMatchingFile(pattern) => {
+                write!(f, "no history file matches {}", pattern.display())
+            }
+            WrappedError::DetectionNeedsFile => write!(
+                f,
+                "the history format can only be detected for a history file, try --shell"
+            ),
+            WrappedError::UnknownFormat(path) => write!(
+                f,
+                "failed to detect the format of {}, try --format",
+                path.display()
+            ),
+            WrappedError::CompressedDatabase(path) => write!(
+                f,
+                "{} is compressed, history databases need to be decompressed first",
+                path.display()
+            ),
+            WrappedError::DatabaseFromStdin => {
+                write!(f, "history databases cannot be read from stdin")
+            }
+            WrappedError::Read { path, source } => {
+                write!(f, "failed to read {}: {}", path.display(), source)
+            }
+            WrappedError::Io(err) => write!(f, "failed to read history: {}", err),
+            WrappedError::Config(path, message) => {
+                write!(f, "invalid config file {}: {}", path.display(), message)
+            }
+        }
+    }
+}
+
+impl Error for WrappedError {}
+
+impl From<io::Error> for WrappedError {
+    fn from(err: io::Error) -> Self {
+        WrappedError::Io(err)
+    }
+}
+
+/// Why a history entry was skipped.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ParseError {
+    Malformed,
+    InvalidTimestamp,
+    NoCommand,
+    NotRawStream(String),
+    MalformedFile,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::Malformed => write!(f, "malformed entry"),
+            ParseError::InvalidTimestamp => write!(f, "invalid timestamp"),
+            ParseError::NoCommand => write!(f, "no command found"),
+            ParseError::NotRawStream(provider) => {
+                write!(f, "{} history is not a raw stream", provider)
+            }
+            ParseError::MalformedFile => write!(f, "malformed file"),
+        }
+    }
+}
+
+impl Error for ParseError {}
+
+#[derive(Default)]
+struct Skipped {
+    count: usize,
+    examples: Vec<String>,
+}
+
+/// Counts the history entries skipped while parsing, per shell and reason.
+#[derive(Default)]
+pub struct Diagnostics {
+    skipped: BTreeMap<(String, ParseError), Skipped>,
+}
+
+impl Diagnostics {
+    const EXAMPLES: usize = 3;
+    const EXAMPLE_LEN: usize = 60;
+
+    pub fn skip(&mut self, source: &str, err: ParseError, raw: &str) {
+        let skipped = self.skipped.entry((source.to_string(), err)).or_default();
+        skipped.count += 1;
+        if skipped.examples.len() < Self::EXAMPLES {
+            skipped.examples.push(Self::redact(raw));
+        }
+    }
+
+    pub fn skipped_count(&self) -> usize {
+        self.skipped.values().map(|skipped| skipped.count).sum()
+    }
+
+    // histories are full of hostnames, paths and tokens, so only the shape
+    // of an entry is shown
+    fn redact(raw: &str) -> String {
+        raw.chars()
+            .take(Self::EXAMPLE_LEN)
+            .map(|c| match c {
+                c if c.is_alphabetic() => 'x',
+                c if c.is_numeric() => '0',
+                c => c,
+            })
+            .collect::<String>()
+            .escape_debug()
+            .to_string()
+    }
+
+    pub fn output(&self, parsed_count: usize) {
+        println!(
+            "{} commands parsed, {} history entries skipped",
+            parsed_count,
+            self.skipped_count()
+        );
+        for ((provider, err), skipped) in &self.skipped {
+            println!("\n{}: {} ({})", provider, err, skipped.count);
+            for example in &skipped.examples {
+                println!("    {}", example);
+            }
+        }
+    }
+}
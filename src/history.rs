@@ -1,12 +1,30 @@
+use chrono::{DateTime, Local};
 use std::{
+    collections::HashMap,
     error::Error,
     fs::File,
-    io::{BufRead, BufReader, Cursor, Read},
-    process::Command,
+    io::{self, Cursor, Read},
+    path::{Path, PathBuf},
     str::FromStr,
+    time::SystemTime,
+    vec,
 };
 
-#[derive(Debug, Clone, strum::Display, strum::EnumString)]
+use crate::{
+    archive::{Archive, Compression},
+    config::PatternProvider,
+    detect::{Detector, SQLITE_MAGIC},
+    discovery::Discovery,
+    error::{Diagnostics, WrappedError},
+    parser::{Command, CommandParser, Resolver},
+    reader::{
+        atuin::AtuinReader, fish::FishReader, hishtory::HishtoryReader, histdb::HistdbReader,
+        ksh::KshReader, mcfly::McFlyReader, nu::NuReader, xonsh::XonshReader,
+    },
+    source::{Entry, HistorySource, PatternHistory, Records, TextHistory},
+};
+
+#[derive(Debug, Clone, PartialEq, strum::Display, strum::EnumString)]
 pub enum HistoryProvider {
     #[strum(serialize = "zsh")]
     Zsh,
@@ -18,115 +36,443 @@ pub enum HistoryProvider {
     Fish,
     #[strum(serialize = "nu")]
     Nu,
+    #[strum(serialize = "pwsh")]
+    Pwsh,
+    #[strum(serialize = "xonsh")]
+    Xonsh,
+    #[strum(to_string = "tcsh", serialize = "csh")]
+    Tcsh,
+    #[strum(to_string = "ksh", serialize = "mksh")]
+    Ksh,
+    #[strum(to_string = "histdb", serialize = "zsh-histdb")]
+    Histdb,
+    #[strum(serialize = "mcfly")]
+    McFly,
+    #[strum(serialize = "hishtory")]
+    Hishtory,
 }
 
 impl HistoryProvider {
-    pub fn from(provider: &String) -> Self {
+    pub fn from(provider: &str) -> Result<Self, WrappedError> {
         HistoryProvider::from_str(provider)
-            .unwrap_or_else(|_| panic!("Sorry, {} is not supported yet\n\n", provider))
+            .map_err(|_| WrappedError::UnsupportedShell(provider.to_string()))
     }
+}
 
-    pub fn history_stream(&self) -> Result<Box<dyn Read>, Box<dyn Error>> {
-        match self {
-            HistoryProvider::Zsh | HistoryProvider::Bash => {
-                let shell = self.to_string();
-                let output = Command::new(shell)
-                    .args(["-i", "-c", r#"echo -e "\n$HISTFILE""#])
-                    .output()?;
-
-                let file_path = std::str::from_utf8(&output.stdout)?
-                    .trim()
-                    .lines()
-                    .last()
-                    .expect("env $HISTFILE not set");
-                Ok(Box::new(File::open(file_path)?))
-            }
-            HistoryProvider::Atuin => {
-                let output = Command::new("atuin")
-                    .args(["history", "list", "--format", "{time};{command}"])
-                    .output()?;
-                Ok(Box::new(Cursor::new(output.stdout)))
+/// One or more histories of the same format, rotated files being read one
+/// after another, oldest first.
+pub struct History {
+    sources: Vec<Box<dyn HistorySource>>,
+}
+
+/// Where a history is read from, `-` standing for stdin. Compressed files
+/// are decompressed on the fly.
+enum Input {
+    File(PathBuf),
+    Archive(PathBuf, Compression),
+    Stdin(Vec<u8>),
+}
+
+impl Input {
+    fn open(path: &Path) -> Result<Self, WrappedError> {
+        let read = |err: io::Error| WrappedError::Read {
+            path: path.to_path_buf(),
+            source: err.into(),
+        };
+        if path == Path::new("-") {
+            let mut bytes = vec![];
+            io::stdin().read_to_end(&mut bytes).map_err(read)?;
+            if let Some(compression) = Compression::detect(&bytes) {
+                let mut decoded = vec![];
+                compression
+                    .decode(Box::new(Cursor::new(bytes)))
+                    .and_then(|mut reader| Ok(reader.read_to_end(&mut decoded)?))
+                    .map_err(|source| WrappedError::Read {
+                        path: path.to_path_buf(),
+                        source,
+                    })?;
+                return Ok(Input::Stdin(decoded));
             }
-            HistoryProvider::Fish => {
-                let output = Command::new("fish")
-                    .arg("-c")
-                    .arg("history --show-time='%s;'")
-                    .output()?;
-                Ok(Box::new(Cursor::new(output.stdout)))
+            return Ok(Input::Stdin(bytes));
+        }
+        if path.is_file() {
+            let mut magic = vec![];
+            File::open(path)
+                .and_then(|file| file.take(8).read_to_end(&mut magic))
+                .map_err(read)?;
+            if let Some(compression) = Compression::detect(&magic) {
+                return Ok(Input::Archive(path.to_path_buf(), compression));
             }
-            HistoryProvider::Nu => {
-                let output = Command::new("nu")
-                .arg("-l")
-                .arg("-c")
-                .arg("history | default [] | each {|i| $\"($i.start_timestamp);($i.command)\"} | table --flatten -i false --theme none")
-                .output()?;
-                Ok(Box::new(Cursor::new(output.stdout)))
+        }
+        Ok(Input::File(path.to_path_buf()))
+    }
+
+    fn header(&self) -> Result<Vec<u8>, WrappedError> {
+        match self {
+            Input::File(path) if path.is_dir() => Ok(vec![]),
+            Input::File(path) | Input::Archive(path, _) => {
+                let mut header = vec![];
+                self.reader()?
+                    .take(4096)
+                    .read_to_end(&mut header)
+                    .map_err(|err| WrappedError::Read {
+                        path: path.clone(),
+                        source: err.into(),
+                    })?;
+                Ok(header)
             }
+            Input::Stdin(bytes) => Ok(bytes.iter().take(4096).copied().collect()),
         }
     }
-}
 
-pub struct History {
-    buff_reader: BufReader<Box<dyn Read>>,
-    provider: HistoryProvider,
+    fn reader(&self) -> Result<Box<dyn Read>, WrappedError> {
+        match self {
+            Input::File(path) => match File::open(path) {
+                Ok(file) => Ok(Box::new(file)),
+                Err(err) => Err(WrappedError::Read {
+                    path: path.clone(),
+                    source: err.into(),
+                }),
+            },
+            Input::Archive(path, compression) => File::open(path)
+                .map_err(Into::into)
+                .and_then(|file| compression.decode(Box::new(file)))
+                .map_err(|source| WrappedError::Read {
+                    path: path.clone(),
+                    source,
+                }),
+            Input::Stdin(bytes) => Ok(Box::new(Cursor::new(bytes.clone()))),
+        }
+    }
+
+    fn stream(self) -> Result<Box<dyn Read>, WrappedError> {
+        match self {
+            Input::Stdin(bytes) => Ok(Box::new(Cursor::new(bytes))),
+            input => input.reader(),
+        }
+    }
+
+    fn modified(&self) -> Option<SystemTime> {
+        match self {
+            Input::File(path) | Input::Archive(path, _) => path
+                .metadata()
+                .and_then(|metadata| metadata.modified())
+                .ok(),
+            Input::Stdin(_) => None,
+        }
+    }
+
+    // databases and session directories can only be read from plain files
+    fn path(&self) -> Result<&Path, WrappedError> {
+        match self {
+            Input::File(path) => Ok(path),
+            Input::Archive(path, _) => Err(WrappedError::CompressedDatabase(path.clone())),
+            Input::Stdin(_) => Err(WrappedError::DatabaseFromStdin),
+        }
+    }
 }
 
 impl History {
-    pub fn from(provider: &HistoryProvider) -> Result<Self, Box<dyn Error>> {
-        Ok(History {
-            provider: provider.clone(),
-            buff_reader: BufReader::new(provider.history_stream()?),
-        })
+    /// Opens the history of `provider`, or detects the format of
+    /// `history_file` when no provider is given. `history_file` may be a glob
+    /// pattern matching rotated and compressed files, read oldest first.
+    pub fn from(
+        provider: Option<&HistoryProvider>,
+        history_file: Option<&Path>,
+    ) -> Result<Self, WrappedError> {
+        let history_file = match (provider, history_file) {
+            (_, Some(path)) => path.to_path_buf(),
+            (Some(provider), None) => Discovery::history_file(provider)
+                .ok_or_else(|| WrappedError::HistoryNotFound(provider.to_string()))?,
+            (None, None) => Err(WrappedError::DetectionNeedsFile)?,
+        };
+        let inputs = Archive::expand(&history_file)?
+            .iter()
+            .map(|path| Input::open(path))
+            .collect::<Result<Vec<_>, _>>()?;
+        // the live file, the newest one, tells the current format
+        let input = inputs
+            .last()
+            .ok_or_else(|| WrappedError::NoMatchingFile(history_file.clone()))?;
+        let provider = match provider {
+            Some(provider) => provider.clone(),
+            None => {
+                let header = input.header()?;
+                if header.starts_with(SQLITE_MAGIC) {
+                    let path = input.path()?;
+                    Detector::detect_database(path).map_err(|source| WrappedError::Read {
+                        path: path.to_path_buf(),
+                        source,
+                    })?
+                } else {
+                    Detector::detect(&header)
+                        .ok_or_else(|| WrappedError::UnknownFormat(history_file.clone()))?
+                }
+            }
+        };
+
+        let sources = inputs
+            .into_iter()
+            .map(|input| Self::source(&provider, input))
+            .collect::<Result<_, _>>()?;
+        Ok(Self::from_sources(sources))
     }
-}
 
-impl Iterator for History {
-    type Item = String;
-
-    fn next(&mut self) -> Option<Self::Item> {
-        match self.provider {
-            HistoryProvider::Zsh
-            | HistoryProvider::Atuin
-            | HistoryProvider::Nu
-            | HistoryProvider::Fish => {
-                let mut block = String::new();
-                let mut buf = vec![];
-                loop {
-                    self.buff_reader.read_until(b'\n', &mut buf).unwrap();
-                    if buf.is_empty() {
-                        return if block.is_empty() { None } else { Some(block) };
-                    }
-                    let str = String::from_utf8_lossy(&buf).trim_end().to_owned();
-                    block += &str;
-                    if str.is_empty() {
-                        buf.clear();
-                        continue;
-                    }
-                    if str.ends_with('\\') {
-                        block = block.strip_suffix('\\')?.into();
-                        buf.clear();
+    /// Opens a log in a format declared in the config file, read from
+    /// `history_file` or the file configured for the provider.
+    pub fn from_pattern(
+        provider: &PatternProvider,
+        history_file: Option<&Path>,
+    ) -> Result<Self, WrappedError> {
+        let history_file = history_file
+            .or(provider.file.as_deref())
+            .ok_or_else(|| WrappedError::HistoryNotFound(provider.name.clone()))?;
+        let sources = Archive::expand(history_file)?
+            .iter()
+            .map(|path| {
+                let input = Input::open(path)?;
+                let modified = input.modified();
+                let history = PatternHistory::new(provider.clone(), input.stream()?);
+                Ok(Box::new(history.modified(modified)) as Box<dyn HistorySource>)
+            })
+            .collect::<Result<_, WrappedError>>()?;
+        Ok(Self::from_sources(sources))
+    }
+
+    /// Reads a history from any sources, such as custom [`HistorySource`]s,
+    /// one after another.
+    pub fn from_sources(sources: Vec<Box<dyn HistorySource>>) -> Self {
+        History { sources }
+    }
+
+    fn source(
+        provider: &HistoryProvider,
+        input: Input,
+    ) -> Result<Box<dyn HistorySource>, WrappedError> {
+        let header = input.header()?;
+        let modified = input.modified();
+        let path = match &input {
+            Input::File(path) | Input::Archive(path, _) => path.clone(),
+            Input::Stdin(_) => PathBuf::from("-"),
+        };
+        let read = |source: Box<dyn Error>| WrappedError::Read {
+            path: path.clone(),
+            source,
+        };
+        let mut skipped = vec![];
+        let records = match provider {
+            HistoryProvider::Atuin if header.starts_with(SQLITE_MAGIC) => {
+                AtuinReader::read(input.path()?).map_err(read)?
+            }
+            // nushell's plaintext `history.txt` is read as a raw stream
+            HistoryProvider::Nu if header.starts_with(SQLITE_MAGIC) => {
+                NuReader::read(input.path()?).map_err(read)?
+            }
+            // `history --show-time='%s;'` output is still accepted as a raw stream
+            HistoryProvider::Fish if header.starts_with(b"- cmd:") => {
+                FishReader::read(input.stream()?).map_err(read)?
+            }
+            // OpenBSD ksh and pdksh write plain text, read as a raw stream
+            HistoryProvider::Ksh if KshReader::is_binary(&header) => {
+                KshReader::read(input.stream()?).map_err(read)?
+            }
+            HistoryProvider::Xonsh => match input {
+                Input::File(file) => XonshReader::read(&file, &mut skipped).map_err(read)?,
+                input => {
+                    let mut bytes = vec![];
+                    input
+                        .stream()?
+                        .read_to_end(&mut bytes)
+                        .map_err(|err| read(err.into()))?;
+                    XonshReader::read_session(&bytes).map_err(read)?
+                }
+            },
+            HistoryProvider::Histdb => HistdbReader::read(input.path()?).map_err(read)?,
+            HistoryProvider::McFly => McFlyReader::read(input.path()?).map_err(read)?,
+            HistoryProvider::Hishtory => HishtoryReader::read(input.path()?).map_err(read)?,
+            _ => {
+                let text = TextHistory::new(provider.clone(), input.stream()?);
+                return Ok(Box::new(text.modified(modified)));
+            }
+        };
+        Ok(Box::new(
+            Records::new(provider, records)
+                .skipped(skipped)
+                .modified(modified),
+        ))
+    }
+
+    /// Parses every history and merges them chronologically. Entries recorded
+    /// by more than one source with the same command and second are kept once.
+    /// With `infer_time`, untimed entries get estimated timestamps first.
+    /// Entries that fail to parse are counted in `diagnostics`, and `resolver`
+    /// finds the program each command runs.
+    pub fn merge(
+        histories: Vec<History>,
+        infer_time: bool,
+        resolver: &Resolver,
+        diagnostics: &mut Diagnostics,
+    ) -> Result<Vec<Command>, WrappedError> {
+        let mut commands = vec![];
+        let mut seen = HashMap::new();
+        for (source, history) in histories.into_iter().enumerate() {
+            let modified = history
+                .sources
+                .iter()
+                .filter_map(|source| source.modified())
+                .max();
+            let mut history_commands = history.parse(resolver, diagnostics)?;
+            if infer_time {
+                Self::infer_time(&mut history_commands, modified);
+            }
+            for command in history_commands {
+                if let Some(time) = command.time.filter(|_| !command.time_estimated) {
+                    let key = (time.timestamp(), command.command_raw.clone());
+                    if *seen.entry(key).or_insert(source) != source {
                         continue;
                     }
-                    break Some(block);
                 }
+                commands.push(command);
             }
-            HistoryProvider::Bash => {
-                let mut block = String::new();
-                let mut buf = vec![];
-                loop {
-                    self.buff_reader.read_until(b'\n', &mut buf).unwrap();
-                    if buf.is_empty() {
-                        return None;
-                    }
-                    let str = String::from_utf8_lossy(&buf).into_owned();
-                    block += &str;
-                    if str.is_empty() || str.starts_with('#') {
-                        buf.clear();
+        }
+        commands.sort_by_key(|command| command.time);
+        Ok(commands)
+    }
+
+    fn parse(
+        self,
+        resolver: &Resolver,
+        diagnostics: &mut Diagnostics,
+    ) -> Result<Vec<Command>, WrappedError> {
+        let mut commands = vec![];
+        for mut source in self.sources {
+            while let Some(entry) = source.next_entry()? {
+                let record = match entry {
+                    Entry::Command(record) => record,
+                    Entry::Skipped { reason, raw } => {
+                        diagnostics.skip(&source.name(), reason, &raw);
                         continue;
                     }
-                    break Some(block);
+                };
+                let raw = record.command_raw.clone();
+                match CommandParser::from_record(*record).parse(source.as_ref(), resolver) {
+                    Ok(parser) => commands.extend(parser.finish()),
+                    Err(reason) => diagnostics.skip(&source.name(), reason, &raw),
                 }
             }
         }
+        Ok(commands)
+    }
+
+    /// Spreads untimed entries evenly between the timed entries around them,
+    /// and between the last timed entry and the file's modification time.
+    /// Entries before the first timestamp stay untimed.
+    fn infer_time(commands: &mut [Command], modified: Option<SystemTime>) {
+        let mut anchors: Vec<_> = commands
+            .iter()
+            .enumerate()
+            .filter_map(|(i, command)| Some((i, command.time?)))
+            .collect();
+        if let (Some(&(_, last)), Some(modified)) = (anchors.last(), modified) {
+            let modified = DateTime::<Local>::from(modified);
+            if modified > last {
+                anchors.push((commands.len(), modified));
+            }
+        }
+        for window in anchors.windows(2) {
+            let ((start, start_time), (end, end_time)) = (window[0], window[1]);
+            let step = (end_time - start_time) / (end - start) as i32;
+            let end = end.min(commands.len());
+            for (offset, command) in commands[start + 1..end].iter_mut().enumerate() {
+                command.time = Some(start_time + step * (offset + 1) as i32);
+                command.time_estimated = true;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, TimeZone};
+
+    fn at(seconds: i64) -> DateTime<Local> {
+        Local.timestamp_opt(1_700_000_000 + seconds, 0).unwrap()
+    }
+
+    fn entries(times: &[Option<i64>]) -> Vec<Command> {
+        times
+            .iter()
+            .map(|time| Command {
+                time: time.map(at),
+                ..Default::default()
+            })
+            .collect()
+    }
+
+    fn times(commands: &[Command]) -> Vec<(Option<DateTime<Local>>, bool)> {
+        commands
+            .iter()
+            .map(|command| (command.time, command.time_estimated))
+            .collect()
+    }
+
+    #[test]
+    fn interpolates_between_anchors() {
+        let mut commands = entries(&[Some(0), None, None, Some(30), None, Some(40)]);
+        History::infer_time(&mut commands, None);
+        assert_eq!(
+            times(&commands),
+            [
+                (Some(at(0)), false),
+                (Some(at(10)), true),
+                (Some(at(20)), true),
+                (Some(at(30)), false),
+                (Some(at(35)), true),
+                (Some(at(40)), false),
+            ]
+        );
+    }
+
+    #[test]
+    fn spreads_tail_until_modified() {
+        let modified = SystemTime::from(at(30));
+        let mut commands = entries(&[Some(0), None, None]);
+        History::infer_time(&mut commands, Some(modified));
+        assert_eq!(
+            times(&commands),
+            [
+                (Some(at(0)), false),
+                (Some(at(10)), true),
+                (Some(at(20)), true),
+            ]
+        );
+
+        // a modification time before the last timestamp leaves the tail alone
+        let modified = SystemTime::from(at(0) - Duration::seconds(1));
+        let mut commands = entries(&[Some(0), None]);
+        History::infer_time(&mut commands, Some(modified));
+        assert_eq!(times(&commands), [(Some(at(0)), false), (None, false)]);
+    }
+
+    #[test]
+    fn leading_entries_stay_untimed() {
+        let modified = SystemTime::from(at(100));
+        let mut commands = entries(&[None, None, Some(0), None, Some(20)]);
+        History::infer_time(&mut commands, Some(modified));
+        assert_eq!(
+            times(&commands),
+            [
+                (None, false),
+                (None, false),
+                (Some(at(0)), false),
+                (Some(at(10)), true),
+                (Some(at(20)), false),
+            ]
+        );
+
+        let mut commands = entries(&[None, None]);
+        History::infer_time(&mut commands, Some(modified));
+        assert_eq!(times(&commands), [(None, false), (None, false)]);
     }
 }
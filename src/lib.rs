@@ -0,0 +1,21 @@
+//! Shell history parsing and statistics behind `cmd-wrapped`.
+//!
+//! [`history::History`] reads the history of a supported shell, or of any
+//! [`source::HistorySource`], [`history::History::merge`] parses and merges
+//! histories into [`parser::Command`]s, and [`stats::Statistic`] aggregates
+//! them into the numbers of the report.
+
+pub mod alias;
+mod archive;
+pub mod config;
+mod detect;
+mod discovery;
+pub mod error;
+pub mod history;
+pub mod parser;
+mod reader;
+pub mod source;
+pub mod stats;
+pub mod subcommand;
+mod view;
+pub mod wrapper;
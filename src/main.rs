@@ -1,32 +1,82 @@
 mod cli;
-mod history;
-mod parser;
-mod stats;
-mod view;
 
 use cli::Cli;
-use history::{History, HistoryProvider};
-use parser::CommandParser;
-use stats::Statistic;
+use cmd_wrapped::{
+    alias::Aliases,
+    config::Config,
+    error::{Diagnostics, WrappedError},
+    history::{History, HistoryProvider},
+    parser::Resolver,
+    stats::Statistic,
+};
+use colored::Colorize;
 
 fn main() {
     let args = Cli::parse_or_default();
-    let provider = HistoryProvider::from(&args.shell);
-    let history = History::from(&provider).expect("failed to read history file");
-    let mut stats = Statistic::from(args.year);
+    if let Err(err) = run(&args) {
+        eprintln!("{} {}", "error:".red().bold(), err);
+        std::process::exit(err.exit_code());
+    }
+}
 
-    for block in history {
-        CommandParser::from_raw(block)
-            .parse(&provider)
-            .unwrap_or_default()
-            .finish()
+fn run(args: &Cli) -> Result<(), WrappedError> {
+    let config = Config::load(args.config.as_deref())?;
+    // stdin is only looked at once, before it is read
+    let sources = args.sources();
+    let histories = sources
+        .iter()
+        .map(|(format, history_file)| {
+            let history_file = history_file.as_deref();
+            if format == "auto" {
+                return History::from(None, history_file);
+            }
+            match (HistoryProvider::from(format), config.provider(format)) {
+                (Ok(provider), _) => History::from(Some(&provider), history_file),
+                (Err(_), Some(provider)) => History::from_pattern(provider, history_file),
+                (Err(err), None) => Err(err),
+            }
+        })
+        .collect::<Result<_, _>>()?;
+
+    let mut aliases = Aliases::default();
+    if !args.no_aliases {
+        let providers: Vec<_> = sources
             .iter()
-            .for_each(|command| stats.analyze(command));
+            .filter_map(|(format, _)| HistoryProvider::from(format).ok())
+            .collect();
+        aliases = Aliases::from_rc_files(&providers);
+        for alias_file in &args.alias_files {
+            aliases.read_file(alias_file)?;
+        }
     }
 
+    let mut diagnostics = Diagnostics::default();
+    let resolver = Resolver {
+        wrappers: config.wrappers().clone(),
+        aliases,
+        tools: config.tools().clone(),
+    };
+    let commands = History::merge(histories, args.infer_time, &resolver, &mut diagnostics)?;
+    if args.diagnose {
+        diagnostics.output(commands.len());
+        return Ok(());
+    }
+
+    let mut stats = Statistic::from(args.year);
+    stats.plot_estimated(args.plot_estimated);
+    commands.iter().for_each(|command| stats.analyze(command));
+
     if args.year == 0 {
         stats.output_recent()
     } else {
         stats.output_annual()
     }
+    if diagnostics.skipped_count() > 0 {
+        eprintln!(
+            "{} {} history entries could not be parsed, run with --diagnose for details",
+            "note:".yellow().bold(),
+            diagnostics.skipped_count()
+        );
+    }
+    Ok(())
 }
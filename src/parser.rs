@@ -1,25 +1,79 @@
-use chrono::{DateTime, Local, NaiveDateTime, TimeZone};
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, NaiveTime, TimeZone};
 use regex::{Captures, Match, Regex};
-use std::{
-    error::Error,
-    time::{Duration, UNIX_EPOCH},
+use std::time::Duration;
+
+use crate::{
+    alias::Aliases, config::PatternProvider, error::ParseError, history::HistoryProvider,
+    source::HistorySource, subcommand::Tools, wrapper::Wrappers,
 };
 
-use crate::history::HistoryProvider;
+pub mod tokenizer;
+
+use tokenizer::{TokenKind, Tokenizer};
 
 lazy_static::lazy_static! {
-    static ref RE_ZSH_HISTORY: Regex = Regex::new(r": (\d+):(\d+);(.+)").unwrap();
-    static ref RE_BASH_HISTORY: Regex = Regex::new(r"#(.*)\n(.+)").unwrap();
-    static ref RE_COMMAND: Regex = Regex::new(r"(?:\||&&)").unwrap();
+    static ref RE_ZSH_HISTORY: Regex = Regex::new(r"(?s): (\d+):(\d+);(.+)").unwrap();
+    static ref RE_BASH_HISTORY: Regex = Regex::new(r"(?s)^#(\d+)\s*\n(.+)").unwrap();
+    static ref RE_TCSH_HISTORY: Regex = Regex::new(r"(?s)^#\+(\d+)\n(.+)").unwrap();
+    // the header of a function definition, as `f()` or `function f`
+    static ref RE_FUNCTION_HEADER: Regex =
+        Regex::new(r"^(?:function\s+\S+(?:\s*\(\s*\))?|[\w.:-]+\s*\(\s*\))$").unwrap();
+    static ref RE_PWSH_PREFIX: Regex = Regex::new(r"^(?:\$[\w:.]+\s*[-+*/]?=\s*)?([&.]\s+)?").unwrap();
+    // numbers, strings, variables, arrays, hash tables and casts
+    static ref RE_PWSH_VALUE: Regex = Regex::new(r#"^(?:-?\d|['"$\[]|@[({])"#).unwrap();
+    static ref RE_PWSH_CMDLET: Regex = Regex::new(r"^[A-Za-z]+-[A-Za-z]\w*$").unwrap();
 }
 
-#[derive(Debug, Default)]
+// reserved words that open, continue or close a compound command in place of
+// a command name, in POSIX shells, fish and tcsh
+const RESERVED_WORDS: [&str; 16] = [
+    "{", "}", "!", "if", "then", "elif", "else", "fi", "while", "until", "do", "done", "esac",
+    "begin", "end", "endif",
+];
+// programs whose single-dash options are words, as the `-name` of `find`
+const SINGLE_DASH_OPTIONS: [&str; 8] = [
+    "find",
+    "java",
+    "go",
+    "terraform",
+    "ffmpeg",
+    "gcc",
+    "clang",
+    "cc",
+];
+// wrappers running the command they are given without alias lookup
+const BYPASS_WRAPPERS: [&str; 2] = ["command", "builtin"];
+// words starting the header of a loop or a case statement
+const HEADER_WORDS: [&str; 5] = ["for", "select", "case", "foreach", "switch"];
+
+#[derive(Debug, Default, Clone)]
 pub struct Command {
     pub command_raw: String,
     pub time: Option<DateTime<Local>>,
+    pub time_estimated: bool,
 
     pub command: String,
     pub arguments: Vec<String>,
+    pub wrappers: Vec<String>,
+    pub alias: Option<String>,
+    pub subcommand: Option<String>,
+    pub flags: Vec<String>,
+
+    pub exit_code: Option<i64>,
+    pub duration: Option<Duration>,
+    pub cwd: Option<String>,
+    pub hostname: Option<String>,
+    pub session: Option<String>,
+    pub paths: Vec<String>,
+    pub shell: String,
+}
+
+/// The tables used to find the program a command runs and its subcommand.
+#[derive(Debug, Clone, Default)]
+pub struct Resolver {
+    pub wrappers: Wrappers,
+    pub aliases: Aliases,
+    pub tools: Tools,
 }
 
 impl Command {
@@ -31,23 +85,110 @@ impl Command {
         }
     }
 
-    fn parse_line(mut self) -> Result<Self, Box<dyn Error>> {
+    fn parse_line(mut self, resolver: &Resolver) -> Result<Self, ParseError> {
         if self.command_raw.is_empty() || self.command_raw.starts_with('#') {
             return Ok(self);
         }
-        let command = self
-            .command_raw
-            .strip_prefix("sudo")
-            .unwrap_or(&self.command_raw);
-        let args: Vec<_> = command.split_whitespace().map(String::from).collect();
-        let c = args
-            .iter()
-            .find(|s| !s.is_empty() && !s.contains('=') && !s.contains('{'))
-            .ok_or("invalid command")?;
-        c.clone_into(&mut self.command);
-        self.arguments = args;
+        let (mut args, mut quoted): (Vec<_>, Vec<_>) = Tokenizer::quoted_words(&self.command_raw)
+            .into_iter()
+            .unzip();
+        let (mut used, index) = resolver.wrappers.unwrap(&args);
+        let mut index = index.ok_or(ParseError::NoCommand)?;
+        // `watch 'df -h'` runs a command line given as a single argument
+        if !used.is_empty() && index + 1 == args.len() && args[index].contains(' ') {
+            let (inner_args, inner_quoted): (Vec<_>, Vec<_>) =
+                Tokenizer::quoted_words(&args[index]).into_iter().unzip();
+            if let (inner, Some(inner_index)) = resolver.wrappers.unwrap(&inner_args) {
+                used.extend(inner);
+                (args, quoted, index) = (inner_args, inner_quoted, inner_index);
+            }
+        }
+        // aliases may expand to other aliases, but not to themselves, as in
+        // `alias ls='ls -G'`. `\ls`, `'ls'` and `command ls` bypass them.
+        let mut expanded: Vec<String> = vec![];
+        let mut bypassed = quoted[index] || Self::bypasses(&used);
+        while let Some(expansion_args) = resolver.aliases.expand(&args[index..]) {
+            if bypassed || expanded.contains(&args[index]) {
+                break;
+            }
+            let (inner, Some(inner_index)) = resolver.wrappers.unwrap(&expansion_args) else {
+                break;
+            };
+            expanded.push(args[index].clone());
+            bypassed = Self::bypasses(&inner);
+            // the `command` of `git() { command git "$@"; }` only keeps the
+            // function from calling itself
+            used.extend(
+                inner
+                    .into_iter()
+                    .filter(|wrapper| !BYPASS_WRAPPERS.contains(&wrapper.as_str())),
+            );
+            (args, index) = (expansion_args, inner_index);
+        }
+        self.alias = expanded
+            .into_iter()
+            .next()
+            .filter(|alias| *alias != args[index]);
+        self.command.clone_from(&args[index]);
+        self.arguments = args.split_off(index);
+        self.subcommand = resolver.tools.subcommand(&self.arguments);
+        // PowerShell parameters are words, as the `-Recurse` of `Get-ChildItem -Recurse`
+        let powershell = self.shell == HistoryProvider::Pwsh.to_string();
+        self.flags = Self::flags(&self.arguments, powershell);
+        self.wrappers = used;
         Ok(self)
     }
+
+    // whether the innermost wrapper runs the command without looking up aliases
+    fn bypasses(wrappers: &[String]) -> bool {
+        wrappers
+            .last()
+            .is_some_and(|wrapper| BYPASS_WRAPPERS.contains(&wrapper.as_str()))
+    }
+
+    /// The options of an argument vector starting with the program, each
+    /// once: short option clusters are split (`-rf` is `-r` and `-f`) and
+    /// values are dropped (`--color=auto` is `--color`, `-j8` is `-j`). With
+    /// `single_dash`, or for programs such as `find`, `-name` is one option.
+    fn flags(args: &[String], single_dash: bool) -> Vec<String> {
+        let Some((program, args)) = args.split_first() else {
+            return vec![];
+        };
+        let single_dash = single_dash
+            || SINGLE_DASH_OPTIONS.contains(&program.rsplit('/').next().unwrap_or_default());
+        let mut flags: Vec<String> = vec![];
+        for arg in args.iter().take_while(|arg| *arg != "--") {
+            let options: Vec<_> = match (arg.strip_prefix("--"), arg.strip_prefix('-')) {
+                (Some(long), _) => {
+                    vec![format!("--{}", long.split('=').next().unwrap_or_default())]
+                }
+                // `-Recurse:$false` in PowerShell
+                (None, Some(short)) if single_dash => {
+                    vec![format!(
+                        "-{}",
+                        short.split(['=', ':']).next().unwrap_or_default()
+                    )]
+                }
+                (None, Some(short)) => short
+                    .chars()
+                    .take_while(char::is_ascii_alphabetic)
+                    .map(|c| format!("-{}", c))
+                    .collect(),
+                (None, None) => continue,
+            };
+            // `-` and negative numbers such as `-10` are operands
+            for option in options {
+                let is_option = option
+                    .trim_start_matches('-')
+                    .starts_with(|c: char| c.is_ascii_alphabetic())
+                    && !option.contains(char::is_whitespace);
+                if is_option && !flags.contains(&option) {
+                    flags.push(option);
+                }
+            }
+        }
+        flags
+    }
 }
 
 type ParsingData = (String, Option<DateTime<Local>>);
@@ -55,6 +196,7 @@ type ParsingData = (String, Option<DateTime<Local>>);
 #[derive(Default)]
 pub struct CommandParser {
     raw: String,
+    record: Option<Command>,
     commands: Vec<Command>,
 }
 
@@ -66,74 +208,213 @@ impl CommandParser {
         }
     }
 
-    pub fn parse(mut self, provider: &HistoryProvider) -> Result<Self, Box<dyn Error>> {
+    pub fn from_record(record: Command) -> Self {
+        CommandParser {
+            record: Some(record),
+            ..Default::default()
+        }
+    }
+
+    /// Reads the command line and timestamp of a raw entry in the format of
+    /// `provider`.
+    pub fn read(&self, provider: &HistoryProvider) -> Result<Command, ParseError> {
         let (commands_combined, time) = match provider {
             HistoryProvider::Zsh => self.parse_zsh_raw(),
             HistoryProvider::Bash => self.parse_bash_raw(),
             HistoryProvider::Atuin => self.parse_atuin_raw(),
             HistoryProvider::Fish => self.parse_fish_raw(),
             HistoryProvider::Nu => self.parse_nu_raw(),
+            HistoryProvider::Pwsh => self.parse_pwsh_raw(),
+            HistoryProvider::Tcsh => self.parse_tcsh_raw(),
+            HistoryProvider::Ksh => self.parse_ksh_raw(),
+            HistoryProvider::Xonsh
+            | HistoryProvider::Histdb
+            | HistoryProvider::McFly
+            | HistoryProvider::Hishtory => Err(ParseError::NotRawStream(provider.to_string()))?,
         }?;
-        let commands_splitted = RE_COMMAND.split(&commands_combined);
-        for commandline in commands_splitted {
-            self.commands
-                .push(Command::from(commandline.into(), time).parse_line()?);
+        Ok(Command::from(commands_combined, time))
+    }
+
+    /// Reads a record of a provider declared in the config file.
+    pub fn read_pattern(&self, provider: &PatternProvider) -> Result<Command, ParseError> {
+        let captures = Re::captures(&provider.pattern, &self.raw)?;
+        let capture = |name| captures.name(name).map(|capture| capture.as_str().trim());
+        let time = match capture("time") {
+            Some(time) => Some(Self::parse_time(time, provider.time_format.as_deref())?),
+            None => None,
+        };
+        Ok(Command {
+            exit_code: capture("exit").and_then(|exit| exit.parse().ok()),
+            cwd: capture("cwd").map(String::from),
+            hostname: capture("host").map(String::from),
+            session: capture("session").map(String::from),
+            ..Command::from(capture("command").unwrap_or_default().into(), time)
+        })
+    }
+
+    // formats without an offset are read as local time, and date-only
+    // formats as midnight
+    fn parse_time(time: &str, format: Option<&str>) -> Result<DateTime<Local>, ParseError> {
+        let format = format.unwrap_or("%s");
+        if let Ok(time) = DateTime::parse_from_str(time, format) {
+            return Ok(time.with_timezone(&Local));
+        }
+        let naive_time = NaiveDateTime::parse_from_str(time, format)
+            .or_else(|_| {
+                NaiveDate::parse_from_str(time, format).map(|date| date.and_time(NaiveTime::MIN))
+            })
+            .map_err(|_| ParseError::InvalidTimestamp)?;
+        Local
+            .from_local_datetime(&naive_time)
+            .earliest()
+            .ok_or(ParseError::InvalidTimestamp)
+    }
+
+    /// Splits the record, or the raw text as an untimed command line, into
+    /// the commands it runs.
+    pub fn parse(
+        mut self,
+        source: &dyn HistorySource,
+        resolver: &Resolver,
+    ) -> Result<Self, ParseError> {
+        let record = match self.record.take() {
+            Some(record) => record,
+            None => Command::from(std::mem::take(&mut self.raw), None),
+        };
+        for (i, commandline) in source.split(&record.command_raw).iter().enumerate() {
+            self.commands.push(
+                Command {
+                    command_raw: commandline.trim().into(),
+                    // the duration and paths cover the whole line, count them only once
+                    duration: record.duration.filter(|_| i == 0),
+                    paths: if i == 0 { record.paths.clone() } else { vec![] },
+                    shell: source.name(),
+                    ..record.clone()
+                }
+                .parse_line(resolver)?,
+            );
         }
         Ok(self)
     }
 
-    pub fn parse_zsh_raw(&self) -> Result<ParsingData, Box<dyn Error>> {
+    /// Splits a command line into the simple commands it runs: on control
+    /// operators outside of quotes, into `( )` subshells and `{ }` groups, and
+    /// into the command lines of substitutions, which follow the command
+    /// they appear in.
+    pub fn split(line: &str) -> Vec<String> {
+        let mut parts = vec![];
+        let mut substitutions = vec![];
+        let mut part: Option<(usize, usize)> = None;
+        let mut parens = 0;
+        // whether the words up to the next `)` are the pattern of a case item
+        let mut pattern = false;
+        let definition = |part: Option<(usize, usize)>| {
+            part.is_some_and(|(start, end)| RE_FUNCTION_HEADER.is_match(&line[start..end]))
+        };
+        for token in Tokenizer::tokenize(line) {
+            for range in &token.substitutions {
+                substitutions.extend(Self::split(&line[range.clone()]));
+            }
+            match token.kind {
+                TokenKind::Word(word) if pattern && word == "esac" => pattern = false,
+                TokenKind::Operator(operator) if pattern => {
+                    part = None;
+                    pattern = operator != ")";
+                }
+                TokenKind::Operator(";;") => {
+                    parts.extend(part.take().map(|(start, end)| line[start..end].to_string()));
+                    parts.append(&mut substitutions);
+                    pattern = true;
+                }
+                // `case word in` is followed by the pattern of the first item
+                TokenKind::Word(word)
+                    if word == "in"
+                        && part.is_some_and(|(start, end)| {
+                            Tokenizer::words(&line[start..end])
+                                .first()
+                                .map(String::as_str)
+                                == Some("case")
+                        }) =>
+                {
+                    part = None;
+                    pattern = true;
+                }
+                TokenKind::Word(word) if part.is_none() && RESERVED_WORDS.contains(&&*word) => {
+                    continue
+                }
+                // the body of a function definition is run when it is called
+                TokenKind::Word(word) if word == "{" && definition(part) => part = None,
+                TokenKind::Operator("(") if definition(part) => part = None,
+                // `(` in the middle of a command is not a subshell, as in the
+                // `foreach f (*.c)` of tcsh
+                TokenKind::Operator("(") if part.is_some() => {
+                    parens += 1;
+                    part = part.map(|(start, _)| (start, token.end));
+                }
+                TokenKind::Operator(")") if parens > 0 => {
+                    parens -= 1;
+                    part = part.map(|(start, _)| (start, token.end));
+                }
+                TokenKind::Operator(_) => {
+                    parts.extend(part.take().map(|(start, end)| line[start..end].to_string()));
+                    parts.append(&mut substitutions);
+                }
+                _ => {
+                    let start = part.map_or(token.start, |(start, _)| start);
+                    part = Some((start, token.end));
+                }
+            }
+        }
+        parts.extend(part.map(|(start, end)| line[start..end].to_string()));
+        parts.append(&mut substitutions);
+        parts
+            .into_iter()
+            // loop and case headers run nothing, nor do lone assignments and
+            // redirections such as the `> out` of `{ ...; } > out`
+            .filter(|part| {
+                let words = Tokenizer::words(part);
+                match words.iter().find(|word| !word.contains('=')) {
+                    Some(word) => !HEADER_WORDS.contains(&word.as_str()),
+                    None => false,
+                }
+            })
+            .collect()
+    }
+
+    pub fn parse_zsh_raw(&self) -> Result<ParsingData, ParseError> {
+        // without EXTENDED_HISTORY, lines carry no `: <start>:<elapsed>;` prefix
+        if !self.raw.starts_with(": ") || !RE_ZSH_HISTORY.is_match(&self.raw) {
+            return Ok((self.raw.clone(), None));
+        }
         let captures = Re::captures(&RE_ZSH_HISTORY, &self.raw)?;
         let (timestamp, commands_raw) = (
             Re::get(&captures, 1)?.as_str(),
             Re::get(&captures, 3)?.as_str().to_string(),
         );
-        let time = Some(DateTime::<Local>::from(
-            UNIX_EPOCH + Duration::from_secs(timestamp.parse::<u64>()?),
-        ));
+        let time = Some(Re::timestamp(timestamp)?);
         Ok((commands_raw, time))
     }
 
-    pub fn parse_bash_raw(&self) -> Result<ParsingData, Box<dyn Error>> {
-        if !&self.raw.starts_with('#') {
+    pub fn parse_bash_raw(&self) -> Result<ParsingData, ParseError> {
+        // lines outside of timestamped entries, comments included
+        let Some(captures) = RE_BASH_HISTORY.captures(&self.raw) else {
             return Ok((self.raw.clone(), None));
-        }
-        let captures = Re::captures(&RE_BASH_HISTORY, &self.raw)?;
+        };
         let (timestamp, commands_raw) = (
             Re::get(&captures, 1)?.as_str(),
             Re::get(&captures, 2)?.as_str().to_string(),
         );
-        let time = Some(DateTime::<Local>::from(
-            UNIX_EPOCH + Duration::from_secs(timestamp.parse::<u64>()?),
-        ));
-        Ok((
-            commands_raw
-                .lines()
-                .find(|item| !item.starts_with('#'))
-                .ok_or("empty command found")?
-                .into(),
-            time,
-        ))
+        let time = Some(Re::timestamp(timestamp)?);
+        Ok((commands_raw, time))
     }
 
-    pub fn parse_nu_raw(&self) -> Result<ParsingData, Box<dyn Error>> {
-        let (time_raw, commands_raw) = self
-            .raw
-            .split_once(';')
-            .ok_or("failed to split nu command")?;
-
-        let time = NaiveDateTime::parse_and_remainder(time_raw.trim(), "%Y-%m-%d %H:%M:%S")
-            .ok()
-            .and_then(|naive_time| Local.from_local_datetime(&naive_time.0).single());
-
-        Ok((commands_raw.trim().into(), time))
+    // reedline's plaintext `history.txt` escapes newlines as `<\n>` and has no timestamps
+    pub fn parse_nu_raw(&self) -> Result<ParsingData, ParseError> {
+        Ok((self.raw.replace("<\\n>", "\n"), None))
     }
 
-    pub fn parse_atuin_raw(&self) -> Result<ParsingData, Box<dyn Error>> {
-        let (time_raw, commands_raw) = self
-            .raw
-            .split_once(';')
-            .ok_or("failed to split atuin command")?;
+    pub fn parse_atuin_raw(&self) -> Result<ParsingData, ParseError> {
+        let (time_raw, commands_raw) = self.raw.split_once(';').ok_or(ParseError::Malformed)?;
 
         let time = NaiveDateTime::parse_from_str(time_raw.trim(), "%Y-%m-%d %H:%M:%S")
             .ok()
@@ -142,18 +423,107 @@ impl CommandParser {
         Ok((commands_raw.trim().into(), time))
     }
 
-    pub fn parse_fish_raw(&self) -> Result<ParsingData, Box<dyn Error>> {
-        let (timestamp, commands_raw) = self
-            .raw
-            .split_once(';')
-            .ok_or("failed to split atuin command")?;
+    pub fn parse_fish_raw(&self) -> Result<ParsingData, ParseError> {
+        let (timestamp, commands_raw) = self.raw.split_once(';').ok_or(ParseError::Malformed)?;
 
-        let time = Some(DateTime::<Local>::from(
-            UNIX_EPOCH + Duration::from_secs(timestamp.parse::<u64>()?),
-        ));
+        let time = Some(Re::timestamp(timestamp)?);
         Ok((commands_raw.into(), time))
     }
 
+    pub fn parse_tcsh_raw(&self) -> Result<ParsingData, ParseError> {
+        if !self.raw.starts_with("#+") {
+            return Ok((self.raw.clone(), None));
+        }
+        let captures = Re::captures(&RE_TCSH_HISTORY, &self.raw)?;
+        let (timestamp, commands_raw) = (
+            Re::get(&captures, 1)?.as_str(),
+            Re::get(&captures, 2)?.as_str().to_string(),
+        );
+        let time = Some(Re::timestamp(timestamp)?);
+        Ok((commands_raw, time))
+    }
+
+    pub fn parse_ksh_raw(&self) -> Result<ParsingData, ParseError> {
+        Ok((self.raw.clone(), None))
+    }
+
+    pub fn parse_pwsh_raw(&self) -> Result<ParsingData, ParseError> {
+        Ok((self.raw.clone(), None))
+    }
+
+    /// Splits a PowerShell line on `|`, `;`, `&&`, `||` and newlines outside of
+    /// quotes, script blocks and subexpressions, then drops variable assignments
+    /// and call operators so that each part starts with the cmdlet name. Parts
+    /// computing a value, as `$x = 5`, are dropped, and cmdlet names are
+    /// lowercased as PowerShell ignores their case.
+    pub fn split_pwsh(line: &str) -> Vec<String> {
+        let mut parts = vec![];
+        let mut current = String::new();
+        let mut quote = None;
+        let mut depth = 0usize;
+        let mut chars = line.chars().peekable();
+        while let Some(c) = chars.next() {
+            if let Some(q) = quote {
+                current.push(c);
+                if c == '`' && q == '"' {
+                    current.extend(chars.next());
+                } else if c == q {
+                    quote = None;
+                }
+                continue;
+            }
+            match c {
+                '`' => {
+                    current.push(c);
+                    current.extend(chars.next());
+                }
+                '\'' | '"' => {
+                    quote = Some(c);
+                    current.push(c);
+                }
+                '{' | '(' => {
+                    depth += 1;
+                    current.push(c);
+                }
+                '}' | ')' => {
+                    depth = depth.saturating_sub(1);
+                    current.push(c);
+                }
+                // a single `&` is the call operator or a background job
+                '&' if depth == 0 && chars.peek() == Some(&'&') => {
+                    chars.next();
+                    parts.push(std::mem::take(&mut current));
+                }
+                '|' if depth == 0 && chars.peek() == Some(&'|') => {
+                    chars.next();
+                    parts.push(std::mem::take(&mut current));
+                }
+                '|' | ';' | '\n' if depth == 0 => parts.push(std::mem::take(&mut current)),
+                _ => current.push(c),
+            }
+        }
+        parts.push(current);
+        parts
+            .iter()
+            .filter_map(|part| {
+                let part = part.trim();
+                let captures = RE_PWSH_PREFIX.captures(part)?;
+                let part = &part[captures.get(0)?.end()..];
+                // the call operator runs a command named by a value, as in
+                // `& "C:\Program Files\Git\bin\git.exe"`
+                if part.is_empty() || captures.get(1).is_none() && RE_PWSH_VALUE.is_match(part) {
+                    return None;
+                }
+                let (name, rest) =
+                    part.split_at(part.find(char::is_whitespace).unwrap_or(part.len()));
+                match RE_PWSH_CMDLET.is_match(name) {
+                    true => Some(name.to_lowercase() + rest),
+                    false => Some(part.to_string()),
+                }
+            })
+            .collect()
+    }
+
     pub fn finish(self) -> Vec<Command> {
         self.commands
     }
@@ -162,15 +532,206 @@ impl CommandParser {
 struct Re;
 
 impl Re {
-    fn captures<'a>(re: &Regex, s: &'a str) -> Result<Captures<'a>, Box<dyn Error>> {
-        Ok(re
-            .captures(s)
-            .ok_or_else(|| format!("incomplete match found: {}", s))?)
+    fn captures<'a>(re: &Regex, s: &'a str) -> Result<Captures<'a>, ParseError> {
+        re.captures(s).ok_or(ParseError::Malformed)
+    }
+
+    fn get<'a>(captures: &Captures<'a>, index: usize) -> Result<Match<'a>, ParseError> {
+        captures.get(index).ok_or(ParseError::Malformed)
+    }
+
+    // seconds since the epoch, rejecting those chrono cannot represent
+    fn timestamp(s: &str) -> Result<DateTime<Local>, ParseError> {
+        let secs: u64 = s.trim().parse().map_err(|_| ParseError::InvalidTimestamp)?;
+        let secs = i64::try_from(secs).map_err(|_| ParseError::InvalidTimestamp)?;
+        Local
+            .timestamp_opt(secs, 0)
+            .single()
+            .ok_or(ParseError::InvalidTimestamp)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn split(line: &str) -> Vec<String> {
+        CommandParser::split(line)
+    }
+
+    fn parse(line: &str, resolver: &Resolver) -> Command {
+        Command {
+            command_raw: line.to_string(),
+            ..Default::default()
+        }
+        .parse_line(resolver)
+        .unwrap()
+    }
+
+    #[test]
+    fn control_operators() {
+        assert_eq!(split("make; make install"), ["make", "make install"]);
+        assert_eq!(split("test -f x || touch x"), ["test -f x", "touch x"]);
+        assert_eq!(split("sleep 5 &"), ["sleep 5"]);
+        assert_eq!(split("ls | grep \"a|b\""), ["ls", "grep \"a|b\""]);
+    }
+
+    #[test]
+    fn substitutions() {
+        assert_eq!(
+            split("cd $(git rev-parse --show-toplevel)"),
+            [
+                "cd $(git rev-parse --show-toplevel)",
+                "git rev-parse --show-toplevel"
+            ]
+        );
+        assert_eq!(split("echo `date`"), ["echo `date`", "date"]);
+        assert_eq!(
+            split(r#"echo $(echo "$(date)")"#),
+            [r#"echo $(echo "$(date)")"#, r#"echo "$(date)""#, "date"]
+        );
+    }
+
+    #[test]
+    fn groups_and_subshells() {
+        assert_eq!(split("(cd src && make)"), ["cd src", "make"]);
+        assert_eq!(split("{ ls; pwd; } > out"), ["ls", "pwd"]);
+    }
+
+    #[test]
+    fn compound_commands() {
+        assert_eq!(split("for f in *.c; do gcc -c $f; done"), ["gcc -c $f"]);
+        assert_eq!(split("if true; then ls; fi"), ["true", "ls"]);
+        assert_eq!(
+            split("case $x in a) echo a;; b|c) echo b;; esac"),
+            ["echo a", "echo b"]
+        );
+        assert_eq!(split("case $x in\n  (a) ls ;;\nesac; pwd"), ["ls", "pwd"]);
+    }
+
+    #[test]
+    fn function_definitions() {
+        assert_eq!(split("f() { ls; }"), ["ls"]);
+        assert_eq!(split("f () { ls; }; f"), ["ls", "f"]);
+        assert_eq!(split("function f { ls; }"), ["ls"]);
+        assert_eq!(split("f() (cd src; make)"), ["cd src", "make"]);
     }
 
-    fn get<'a>(captures: &Captures<'a>, index: usize) -> Result<Match<'a>, Box<dyn Error>> {
-        Ok(captures
-            .get(index)
-            .ok_or("failed to get match from re capture")?)
+    #[test]
+    fn assignments() {
+        assert_eq!(split("a=1 && ls"), ["ls"]);
+        assert_eq!(split("x=( a b ); ls"), ["ls"]);
+    }
+
+    #[test]
+    fn aliases() {
+        let mut resolver = Resolver::default();
+        resolver.aliases.insert("ls", "ls -G");
+        resolver.aliases.insert("ll", "ls -l");
+        let command = parse("ll -a", &resolver);
+        assert_eq!(command.arguments, ["ls", "-G", "-l", "-a"]);
+        assert_eq!(command.alias.as_deref(), Some("ll"));
+        assert_eq!(parse("\\ls -a", &resolver).arguments, ["ls", "-a"]);
+        assert_eq!(parse("'ls' -a", &resolver).arguments, ["ls", "-a"]);
+        let command = parse("command ls -a", &resolver);
+        assert_eq!(command.arguments, ["ls", "-a"]);
+        assert_eq!(command.wrappers, ["command"]);
+    }
+
+    #[test]
+    fn functions() {
+        let mut resolver = Resolver::default();
+        resolver
+            .aliases
+            .insert_function("git", "command git \"$@\"");
+        resolver
+            .aliases
+            .insert_function("mkcd", "mkdir -p \"$1\" && cd \"$1\"");
+        let command = parse("git commit --amend", &resolver);
+        assert_eq!(command.arguments, ["git", "commit", "--amend"]);
+        assert_eq!(command.subcommand.as_deref(), Some("commit"));
+        assert!(command.wrappers.is_empty());
+        assert_eq!(
+            parse("mkcd foo", &resolver).arguments,
+            ["mkdir", "-p", "foo"]
+        );
+        assert_eq!(parse("mkcd", &resolver).arguments, ["mkdir", "-p"]);
+    }
+
+    #[test]
+    fn wrappers() {
+        let resolver = Resolver::default();
+        let command = parse("sudo -u deploy FOO=1 nice -n 10 make", &resolver);
+        assert_eq!(command.command, "make");
+        assert_eq!(command.wrappers, ["sudo", "nice"]);
+        let command = parse("command -v ls", &resolver);
+        assert_eq!(command.arguments, ["command", "-v", "ls"]);
+        assert!(command.wrappers.is_empty());
+    }
+
+    #[test]
+    fn pwsh() {
+        assert_eq!(
+            CommandParser::split_pwsh(
+                "$files = Get-ChildItem; $files | Where-Object { $_.Length -gt 0 }"
+            ),
+            ["get-childitem", "where-object { $_.Length -gt 0 }"]
+        );
+        assert_eq!(
+            CommandParser::split_pwsh("$x = 5; $env:PATH += \";x\""),
+            Vec::<String>::new()
+        );
+        assert_eq!(
+            CommandParser::split_pwsh("& \"C:\\tools\\x.exe\" -v; [int]$y = 1"),
+            ["\"C:\\tools\\x.exe\" -v"]
+        );
+    }
+
+    fn flags(line: &str) -> Vec<String> {
+        Command::flags(&Tokenizer::words(line), false)
+    }
+
+    #[test]
+    fn short_clusters_are_split() {
+        assert_eq!(flags("rm -rf build"), ["-r", "-f"]);
+        assert_eq!(flags("tar -xzvf a.tgz -v"), ["-x", "-z", "-v", "-f"]);
+    }
+
+    #[test]
+    fn values_are_dropped() {
+        assert_eq!(flags("ls --color=auto"), ["--color"]);
+        assert_eq!(flags("make -j8"), ["-j"]);
+        assert_eq!(flags("git log -n 5"), ["-n"]);
+    }
+
+    #[test]
+    fn operands_are_not_options() {
+        assert_eq!(flags("grep -v -- -x file"), ["-v"]);
+        assert_eq!(flags("tail -n -10 - file"), ["-n"]);
+        assert_eq!(flags("sort -"), Vec::<String>::new());
+    }
+
+    #[test]
+    fn single_dash_options() {
+        assert_eq!(flags("find . -name '*.rs' -type f"), ["-name", "-type"]);
+        assert_eq!(
+            Command::flags(
+                &Tokenizer::words("Get-ChildItem -Recurse -Force -Depth:2"),
+                true
+            ),
+            ["-Recurse", "-Force", "-Depth"]
+        );
+    }
+
+    #[test]
+    fn pwsh_parameters() {
+        let command = Command {
+            command_raw: "Get-ChildItem -Recurse -Force".to_string(),
+            shell: HistoryProvider::Pwsh.to_string(),
+            ..Default::default()
+        }
+        .parse_line(&Resolver::default())
+        .unwrap();
+        assert_eq!(command.flags, ["-Recurse", "-Force"]);
     }
 }
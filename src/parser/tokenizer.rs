@@ -0,0 +1,497 @@
+use std::ops::Range;
+
+/// A token of a command line, with the byte range it was read from.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub start: usize,
+    pub end: usize,
+    /// Byte ranges of the command lines run by the `$(...)`, backtick and
+    /// `<(...)` substitutions of a word.
+    pub substitutions: Vec<Range<usize>>,
+    /// Whether a word was quoted or escaped in part, as `'ls'` or `\ls`,
+    /// which keeps the shell from expanding it as an alias.
+    pub quoted: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TokenKind {
+    /// A word, with quotes and escapes removed. Substitutions are kept as
+    /// written.
+    Word(String),
+    /// A control operator, such as `|`, `&&`, `;` or a newline.
+    Operator(&'static str),
+    /// A redirection operator, such as `>` or the `>&` of `2>&1`. The word
+    /// after it is its target.
+    Redirect(&'static str),
+}
+
+const OPERATORS: [&str; 10] = ["&&", "||", ";;", "|&", "|", "&", ";", "\n", "(", ")"];
+const REDIRECTS: [&str; 12] = [
+    "&>>", "<<<", "<<-", "&>", ">>", ">&", ">|", "<<", "<&", "<>", ">", "<",
+];
+
+/// A POSIX-ish shell lexer, aware of single and double quotes, backslash
+/// escapes, `$'...'` strings, substitutions, comments and heredocs.
+pub struct Tokenizer<'a> {
+    line: &'a str,
+    pos: usize,
+    // delimiters of the heredocs whose bodies start after the next newline,
+    // and whether their leading tabs are stripped
+    heredocs: Vec<(String, bool)>,
+    // substitutions of the word being read
+    substitutions: Vec<Range<usize>>,
+    // whether the word being read has quotes or escapes
+    quoted: bool,
+}
+
+impl<'a> Tokenizer<'a> {
+    pub fn tokenize(line: &'a str) -> Vec<Token> {
+        let mut tokenizer = Tokenizer {
+            line,
+            pos: 0,
+            heredocs: vec![],
+            substitutions: vec![],
+            quoted: false,
+        };
+        let mut tokens: Vec<Token> = vec![];
+        while let Some(token) = tokenizer.next_token() {
+            if let (Some(last), TokenKind::Word(word)) = (tokens.last(), &token.kind) {
+                match last.kind {
+                    TokenKind::Redirect("<<") => tokenizer.heredocs.push((word.clone(), false)),
+                    TokenKind::Redirect("<<-") => tokenizer.heredocs.push((word.clone(), true)),
+                    _ => {}
+                }
+            }
+            tokens.push(token);
+        }
+        tokens
+    }
+
+    /// The argument vector of a simple command: its words, without the
+    /// redirections and their targets.
+    pub fn words(line: &str) -> Vec<String> {
+        Self::quoted_words(line)
+            .into_iter()
+            .map(|(word, _)| word)
+            .collect()
+    }
+
+    /// Like [`Tokenizer::words`], with whether each word was quoted or
+    /// escaped in part.
+    pub fn quoted_words(line: &str) -> Vec<(String, bool)> {
+        let mut words = vec![];
+        let mut redirect = false;
+        for token in Tokenizer::tokenize(line) {
+            match token.kind {
+                TokenKind::Word(_) if redirect => redirect = false,
+                TokenKind::Word(word) => words.push((word, token.quoted)),
+                TokenKind::Redirect(_) => redirect = true,
+                TokenKind::Operator(_) => redirect = false,
+            }
+        }
+        words
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.line[self.pos..]
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.rest().chars().next()
+    }
+
+    fn peek_nth(&self, n: usize) -> Option<char> {
+        self.rest().chars().nth(n)
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += c.len_utf8();
+        Some(c)
+    }
+
+    fn next_token(&mut self) -> Option<Token> {
+        loop {
+            match self.peek()? {
+                ' ' | '\t' | '\r' => self.pos += 1,
+                '\\' if self.peek_nth(1) == Some('\n') => self.pos += 2,
+                '#' => self.pos += self.rest().find('\n').unwrap_or(self.rest().len()),
+                _ => break,
+            }
+        }
+        let start = self.pos;
+        let kind = if let Some(redirect) = self.redirect() {
+            TokenKind::Redirect(redirect)
+        } else if let Some(operator) = self.operator() {
+            if operator == "\n" {
+                self.skip_heredocs();
+            }
+            TokenKind::Operator(operator)
+        } else {
+            let word = self.word();
+            // a stray character no token starts with
+            if self.pos == start {
+                self.bump();
+            }
+            TokenKind::Word(word)
+        };
+        Some(Token {
+            kind,
+            start,
+            end: self.pos,
+            substitutions: std::mem::take(&mut self.substitutions),
+            quoted: std::mem::take(&mut self.quoted),
+        })
+    }
+
+    fn operator(&mut self) -> Option<&'static str> {
+        let operator = OPERATORS
+            .into_iter()
+            .find(|operator| self.rest().starts_with(operator))?;
+        self.pos += operator.len();
+        Some(operator)
+    }
+
+    // redirections may start with a file descriptor, as in `2>`
+    fn redirect(&mut self) -> Option<&'static str> {
+        let digits = self.rest().len()
+            - self
+                .rest()
+                .trim_start_matches(|c: char| c.is_ascii_digit())
+                .len();
+        let rest = &self.rest()[digits..];
+        let redirect = REDIRECTS
+            .into_iter()
+            .find(|redirect| rest.starts_with(redirect))?;
+        // `<(...)` and `>(...)` are process substitutions
+        if rest[redirect.len()..].starts_with('(') && !redirect.starts_with('&') {
+            return None;
+        }
+        self.pos += digits + redirect.len();
+        Some(redirect)
+    }
+
+    fn skip_heredocs(&mut self) {
+        for (delimiter, strip_tabs) in std::mem::take(&mut self.heredocs) {
+            while self.pos < self.line.len() {
+                let line_end = self.rest().find('\n').unwrap_or(self.rest().len());
+                let line = &self.rest()[..line_end];
+                let line = if strip_tabs {
+                    line.trim_start_matches('\t')
+                } else {
+                    line
+                };
+                let found = line == delimiter;
+                self.pos = (self.pos + line_end + 1).min(self.line.len());
+                if found {
+                    break;
+                }
+            }
+        }
+    }
+
+    fn word(&mut self) -> String {
+        let mut word = String::new();
+        while let Some(c) = self.peek() {
+            match c {
+                ' ' | '\t' | '\r' | '\n' | ';' | '&' | '|' | ')' => break,
+                '<' | '>' if self.peek_nth(1) == Some('(') => word += self.substitution(),
+                '<' | '>' => break,
+                // arrays and glob qualifiers, as in `arr=(a b)` or `*(.)`
+                '(' if !word.is_empty() => word += self.balanced(0),
+                '(' => break,
+                '\'' => {
+                    self.bump();
+                    self.quoted = true;
+                    let end = self.rest().find('\'').unwrap_or(self.rest().len());
+                    word += &self.rest()[..end];
+                    self.pos = (self.pos + end + 1).min(self.line.len());
+                }
+                '"' => {
+                    self.bump();
+                    self.quoted = true;
+                    self.double_quoted(&mut word);
+                }
+                '\\' => {
+                    self.bump();
+                    self.quoted = true;
+                    match self.bump() {
+                        Some('\n') => {}
+                        Some(c) => word.push(c),
+                        None => word.push('\\'),
+                    }
+                }
+                '$' => match self.peek_nth(1) {
+                    Some('\'') => {
+                        self.pos += 2;
+                        self.quoted = true;
+                        self.ansi_c_quoted(&mut word);
+                    }
+                    Some('"') => {
+                        self.pos += 2;
+                        self.quoted = true;
+                        self.double_quoted(&mut word);
+                    }
+                    Some('(') => word += self.substitution(),
+                    Some('{') => word += self.balanced(1),
+                    _ => {
+                        self.bump();
+                        word.push('$');
+                    }
+                },
+                '`' => word += self.substitution(),
+                _ => {
+                    self.bump();
+                    word.push(c);
+                }
+            }
+        }
+        word
+    }
+
+    // reads up to the closing quote, the opening one being consumed
+    fn double_quoted(&mut self, word: &mut String) {
+        while let Some(c) = self.peek() {
+            match c {
+                '"' => {
+                    self.bump();
+                    return;
+                }
+                '\\' => {
+                    self.bump();
+                    match self.bump() {
+                        Some('\n') => {}
+                        Some(c @ ('$' | '`' | '"' | '\\')) => word.push(c),
+                        Some(c) => {
+                            word.push('\\');
+                            word.push(c);
+                        }
+                        None => word.push('\\'),
+                    }
+                }
+                '$' if self.peek_nth(1) == Some('(') => *word += self.substitution(),
+                '$' if self.peek_nth(1) == Some('{') => *word += self.balanced(1),
+                '`' => *word += self.substitution(),
+                _ => {
+                    self.bump();
+                    word.push(c);
+                }
+            }
+        }
+    }
+
+    // reads up to the closing quote of `$'...'`, the opening one being consumed
+    fn ansi_c_quoted(&mut self, word: &mut String) {
+        while let Some(c) = self.bump() {
+            match c {
+                '\'' => return,
+                '\\' => match self.bump() {
+                    Some('n') => word.push('\n'),
+                    Some('t') => word.push('\t'),
+                    Some('r') => word.push('\r'),
+                    Some('a') => word.push('\x07'),
+                    Some('b') => word.push('\x08'),
+                    Some('e') | Some('E') => word.push('\x1b'),
+                    Some('f') => word.push('\x0c'),
+                    Some('v') => word.push('\x0b'),
+                    Some('x') => {
+                        let digits: String = self
+                            .rest()
+                            .chars()
+                            .take(2)
+                            .take_while(char::is_ascii_hexdigit)
+                            .collect();
+                        self.pos += digits.len();
+                        match u32::from_str_radix(&digits, 16)
+                            .ok()
+                            .and_then(char::from_u32)
+                        {
+                            Some(c) => word.push(c),
+                            None => word.push_str("\\x"),
+                        }
+                    }
+                    Some(c) => word.push(c),
+                    None => word.push('\\'),
+                },
+                _ => word.push(c),
+            }
+        }
+    }
+
+    /// Reads a `$(...)`, `<(...)`, `>(...)` or backtick substitution and
+    /// records the command line inside. `$((...))` is arithmetic, not a
+    /// command.
+    fn substitution(&mut self) -> &'a str {
+        let text = match self.peek() {
+            Some('`') => self.backticks(),
+            _ => self.balanced(1),
+        };
+        let start = self.pos - text.len();
+        let closed = text.len() > 2 && (text.ends_with(')') || text.ends_with('`'));
+        if closed && !text.starts_with("$((") {
+            let open = if text.starts_with('`') { 1 } else { 2 };
+            self.substitutions.push(start + open..self.pos - 1);
+        }
+        text
+    }
+
+    /// Reads the bracketed text starting `offset` bytes ahead, such as
+    /// `$(...)` or `${...}`, up to the matching bracket. Quotes and escapes
+    /// inside are skipped over.
+    fn balanced(&mut self, offset: usize) -> &'a str {
+        let start = self.pos;
+        self.pos += offset;
+        let (open, close) = match self.bump() {
+            Some('{') => ('{', '}'),
+            _ => ('(', ')'),
+        };
+        let mut depth = 1;
+        while let Some(c) = self.bump() {
+            match c {
+                '\\' => {
+                    self.bump();
+                }
+                '\'' => {
+                    let end = self.rest().find('\'').unwrap_or(self.rest().len());
+                    self.pos = (self.pos + end + 1).min(self.line.len());
+                }
+                // substitutions nested in this one are found when its own
+                // command line is split
+                '"' => {
+                    let recorded = self.substitutions.len();
+                    self.double_quoted(&mut String::new());
+                    self.substitutions.truncate(recorded);
+                }
+                '`' => {
+                    self.pos -= 1;
+                    self.backticks();
+                }
+                c if c == open => depth += 1,
+                c if c == close => {
+                    depth -= 1;
+                    if depth == 0 {
+                        break;
+                    }
+                }
+                _ => {}
+            }
+        }
+        &self.line[start..self.pos]
+    }
+
+    fn backticks(&mut self) -> &'a str {
+        let start = self.pos;
+        self.bump();
+        while let Some(c) = self.bump() {
+            match c {
+                '\\' => {
+                    self.bump();
+                }
+                '`' => break,
+                _ => {}
+            }
+        }
+        &self.line[start..self.pos]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kinds(line: &str) -> Vec<TokenKind> {
+        Tokenizer::tokenize(line)
+            .into_iter()
+            .map(|token| token.kind)
+            .collect()
+    }
+
+    fn substitutions(line: &str) -> Vec<&str> {
+        Tokenizer::tokenize(line)
+            .iter()
+            .flat_map(|token| token.substitutions.clone())
+            .map(|range| &line[range])
+            .collect()
+    }
+
+    #[test]
+    fn quotes_and_escapes() {
+        assert_eq!(
+            Tokenizer::words(r#"grep "a|b" 'c;d'"#),
+            ["grep", "a|b", "c;d"]
+        );
+        assert_eq!(Tokenizer::words(r"echo a\ b \$x"), ["echo", "a b", "$x"]);
+        assert_eq!(Tokenizer::words(r"printf $'a\tb'"), ["printf", "a\tb"]);
+    }
+
+    #[test]
+    fn quoted_words() {
+        assert_eq!(
+            Tokenizer::quoted_words(r"\ls 'ls' l's' ls"),
+            [
+                ("ls".into(), true),
+                ("ls".into(), true),
+                ("ls".into(), true),
+                ("ls".into(), false)
+            ]
+        );
+    }
+
+    #[test]
+    fn operators() {
+        assert_eq!(
+            kinds("test -f x || touch x &"),
+            [
+                TokenKind::Word("test".into()),
+                TokenKind::Word("-f".into()),
+                TokenKind::Word("x".into()),
+                TokenKind::Operator("||"),
+                TokenKind::Word("touch".into()),
+                TokenKind::Word("x".into()),
+                TokenKind::Operator("&"),
+            ]
+        );
+    }
+
+    #[test]
+    fn redirections() {
+        assert_eq!(Tokenizer::words("make 2>&1 >out.log"), ["make"]);
+        assert_eq!(Tokenizer::words("diff <(ls a) b"), ["diff", "<(ls a)", "b"]);
+    }
+
+    #[test]
+    fn heredocs() {
+        assert_eq!(
+            kinds("cat <<EOF\na; b\nEOF\nls").last(),
+            Some(&TokenKind::Word("ls".into()))
+        );
+    }
+
+    #[test]
+    fn substitutions_are_kept_as_written() {
+        assert_eq!(
+            Tokenizer::words("echo $(date) `id -u` ${HOME}"),
+            ["echo", "$(date)", "`id -u`", "${HOME}"]
+        );
+    }
+
+    #[test]
+    fn substitutions_are_recorded() {
+        assert_eq!(
+            substitutions("echo $(git rev-parse HEAD)"),
+            ["git rev-parse HEAD"]
+        );
+        assert_eq!(substitutions("echo `date`"), ["date"]);
+        assert_eq!(substitutions("echo \"$(date)\""), ["date"]);
+        assert_eq!(substitutions("echo $((1 + 2))"), Vec::<&str>::new());
+    }
+
+    #[test]
+    fn nested_substitutions_are_recorded_once() {
+        assert_eq!(
+            substitutions(r#"echo $(echo "$(date)")"#),
+            [r#"echo "$(date)""#]
+        );
+    }
+}
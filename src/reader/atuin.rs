@@ -0,0 +1,41 @@
+use chrono::{Local, TimeZone};
+use rusqlite::{Connection, OpenFlags};
+use std::{error::Error, path::Path, time::Duration};
+
+use crate::parser::Command;
+
+/// Reads atuin's `history.db` directly, keeping everything atuin records
+/// about a command instead of only its timestamp.
+pub struct AtuinReader;
+
+impl AtuinReader {
+    pub fn read(path: &Path) -> Result<Vec<Command>, Box<dyn Error>> {
+        let conn = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+        let mut stmt = conn.prepare(
+            "SELECT timestamp, duration, exit, command, cwd, session, hostname
+            FROM history
+            WHERE deleted_at IS NULL
+            ORDER BY timestamp",
+        )?;
+        let commands = stmt
+            .query_map([], |row| {
+                let hostname: String = row.get(6)?;
+                Ok(Command {
+                    command_raw: row.get(3)?,
+                    time: Some(Local.timestamp_nanos(row.get(0)?)),
+                    // atuin stores -1 for commands that never reported back
+                    duration: u64::try_from(row.get::<_, i64>(1)?)
+                        .ok()
+                        .map(Duration::from_nanos),
+                    exit_code: Some(row.get::<_, i64>(2)?).filter(|exit| *exit >= 0),
+                    cwd: row.get(4)?,
+                    session: row.get(5)?,
+                    // atuin records the host as `hostname:username`
+                    hostname: hostname.split(':').next().map(String::from),
+                    ..Default::default()
+                })
+            })?
+            .collect::<Result<_, _>>()?;
+        Ok(commands)
+    }
+}
@@ -0,0 +1,76 @@
+use chrono::{Local, TimeZone};
+use std::{
+    error::Error,
+    io::{BufRead, BufReader, Read},
+};
+
+use crate::parser::Command;
+
+/// Reads the YAML-like `fish_history` file:
+///
+/// ```text
+/// - cmd: cat notes.txt\nls
+///   when: 1700000000
+///   paths:
+///     - notes.txt
+/// ```
+pub struct FishReader;
+
+impl FishReader {
+    pub fn read(stream: impl Read) -> Result<Vec<Command>, Box<dyn Error>> {
+        let mut commands = vec![];
+        let mut buff_reader = BufReader::new(stream);
+        let mut buf = vec![];
+        loop {
+            buf.clear();
+            if buff_reader.read_until(b'\n', &mut buf)? == 0 {
+                break;
+            }
+            let line = String::from_utf8_lossy(&buf);
+            let line = line.trim_end_matches(['\n', '\r']);
+
+            if let Some(cmd) = line.strip_prefix("- cmd:") {
+                commands.push(Command {
+                    command_raw: Self::unescape(cmd.strip_prefix(' ').unwrap_or(cmd)),
+                    ..Default::default()
+                });
+                continue;
+            }
+            let Some(command) = commands.last_mut() else {
+                continue;
+            };
+            if let Some(when) = line.strip_prefix("  when:") {
+                command.time = when
+                    .trim()
+                    .parse()
+                    .ok()
+                    .and_then(|timestamp| Local.timestamp_opt(timestamp, 0).single());
+            } else if let Some(path) = line.strip_prefix("    - ") {
+                command.paths.push(Self::unescape(path));
+            }
+        }
+        Ok(commands)
+    }
+
+    // fish escapes only backslashes and newlines
+    fn unescape(s: &str) -> String {
+        let mut res = String::with_capacity(s.len());
+        let mut chars = s.chars();
+        while let Some(c) = chars.next() {
+            if c != '\\' {
+                res.push(c);
+                continue;
+            }
+            match chars.next() {
+                Some('n') => res.push('\n'),
+                Some('\\') => res.push('\\'),
+                Some(c) => {
+                    res.push('\\');
+                    res.push(c);
+                }
+                None => res.push('\\'),
+            }
+        }
+        res
+    }
+}
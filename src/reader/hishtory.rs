@@ -0,0 +1,46 @@
+use chrono::{DateTime, Local};
+use rusqlite::{Connection, OpenFlags};
+use std::{error::Error, path::Path};
+
+use crate::parser::Command;
+
+/// Reads hishtory's local `.hishtory.db`.
+pub struct HishtoryReader;
+
+impl HishtoryReader {
+    pub fn read(path: &Path) -> Result<Vec<Command>, Box<dyn Error>> {
+        let conn = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+        let mut stmt = conn.prepare(
+            "SELECT start_time, end_time, exit_code, command, current_working_directory, hostname
+            FROM history_entries
+            ORDER BY start_time",
+        )?;
+        let commands = stmt
+            .query_map([], |row| {
+                let start_time = Self::parse_time(row.get(0)?);
+                let end_time = Self::parse_time(row.get(1)?);
+                Ok(Command {
+                    command_raw: row.get(3)?,
+                    time: start_time,
+                    duration: start_time
+                        .zip(end_time)
+                        .and_then(|(start, end)| (end - start).to_std().ok()),
+                    exit_code: row.get(2)?,
+                    cwd: row.get(4)?,
+                    hostname: row.get(5)?,
+                    ..Default::default()
+                })
+            })?
+            .collect::<Result<_, _>>()?;
+        Ok(commands)
+    }
+
+    // GORM stores times as text, e.g. `2024-01-02 15:04:05.123456789-08:00`
+    fn parse_time(time: Option<String>) -> Option<DateTime<Local>> {
+        let time = time?;
+        DateTime::parse_from_str(&time, "%Y-%m-%d %H:%M:%S%.f%:z")
+            .or_else(|_| DateTime::parse_from_rfc3339(&time))
+            .ok()
+            .map(DateTime::from)
+    }
+}
@@ -0,0 +1,44 @@
+use chrono::{Local, TimeZone};
+use rusqlite::{Connection, OpenFlags};
+use std::{error::Error, path::Path, time::Duration};
+
+use crate::parser::Command;
+
+/// Reads the database of the zsh-histdb plugin, where commands and places
+/// are normalised into their own tables.
+pub struct HistdbReader;
+
+impl HistdbReader {
+    pub fn read(path: &Path) -> Result<Vec<Command>, Box<dyn Error>> {
+        let conn = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+        let mut stmt = conn.prepare(
+            "SELECT history.start_time, history.duration, history.exit_status, commands.argv,
+                places.dir, history.session, places.host
+            FROM history
+            JOIN commands ON history.command_id = commands.id
+            LEFT JOIN places ON history.place_id = places.id
+            ORDER BY history.start_time",
+        )?;
+        let commands = stmt
+            .query_map([], |row| {
+                Ok(Command {
+                    command_raw: row.get(3)?,
+                    time: row
+                        .get::<_, Option<i64>>(0)?
+                        .and_then(|timestamp| Local.timestamp_opt(timestamp, 0).single()),
+                    // commands still running when the shell exited have no duration
+                    duration: row
+                        .get::<_, Option<i64>>(1)?
+                        .and_then(|duration| u64::try_from(duration).ok())
+                        .map(Duration::from_secs),
+                    exit_code: row.get(2)?,
+                    cwd: row.get(4)?,
+                    session: row.get::<_, Option<i64>>(5)?.map(|id| id.to_string()),
+                    hostname: row.get(6)?,
+                    ..Default::default()
+                })
+            })?
+            .collect::<Result<_, _>>()?;
+        Ok(commands)
+    }
+}
@@ -0,0 +1,59 @@
+use std::{error::Error, io::Read};
+
+use crate::parser::Command;
+
+const MKSH_MAGIC: &[u8] = &[0xab, 0xcd];
+const KSH93_MAGIC: &[u8] = &[0x81, 0x01];
+
+/// Reads the binary history files of mksh and ksh93. Neither records
+/// timestamps, and plain-text ksh history is read as a raw stream instead.
+pub struct KshReader;
+
+impl KshReader {
+    pub fn is_binary(header: &[u8]) -> bool {
+        header.starts_with(MKSH_MAGIC) || header.starts_with(KSH93_MAGIC)
+    }
+
+    pub fn read(mut stream: impl Read) -> Result<Vec<Command>, Box<dyn Error>> {
+        let mut bytes = vec![];
+        stream.read_to_end(&mut bytes)?;
+        let entries = if let Some(records) = bytes.strip_prefix(MKSH_MAGIC) {
+            Self::mksh_entries(records)
+        } else if let Some(records) = bytes.strip_prefix(KSH93_MAGIC) {
+            Self::ksh93_entries(records)
+        } else {
+            Err("unknown ksh history format")?
+        };
+        Ok(entries
+            .iter()
+            .map(|entry| String::from_utf8_lossy(entry).trim().to_string())
+            .filter(|entry| !entry.is_empty())
+            .map(|command_raw| Command {
+                command_raw,
+                ..Default::default()
+            })
+            .collect())
+    }
+
+    // each record is 0xff, a 4-byte line number and a NUL-terminated command
+    fn mksh_entries(mut records: &[u8]) -> Vec<&[u8]> {
+        let mut entries = vec![];
+        while let Some(start) = records.iter().position(|&b| b == 0xff) {
+            let Some(record) = records.get(start + 5..) else {
+                break;
+            };
+            let end = record.iter().position(|&b| b == 0).unwrap_or(record.len());
+            entries.push(&record[..end]);
+            records = &record[(end + 1).min(record.len())..];
+        }
+        entries
+    }
+
+    // commands end with a newline, NUL bytes mark ksh93's bookkeeping entries
+    fn ksh93_entries(records: &[u8]) -> Vec<&[u8]> {
+        records
+            .split(|&b| b == b'\n')
+            .filter(|entry| !entry.contains(&0))
+            .collect()
+    }
+}
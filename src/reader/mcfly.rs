@@ -0,0 +1,38 @@
+use chrono::{Local, TimeZone};
+use rusqlite::{Connection, OpenFlags};
+use std::{error::Error, path::Path, time::Duration};
+
+use crate::parser::Command;
+
+/// Reads McFly's `history.db`. McFly does not record the host.
+pub struct McFlyReader;
+
+impl McFlyReader {
+    pub fn read(path: &Path) -> Result<Vec<Command>, Box<dyn Error>> {
+        let conn = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+        let mut stmt = conn.prepare(
+            "SELECT when_run, end_time, exit_code, cmd, dir, session_id
+            FROM commands
+            ORDER BY when_run",
+        )?;
+        let commands = stmt
+            .query_map([], |row| {
+                let when_run: Option<i64> = row.get(0)?;
+                let end_time: Option<i64> = row.get(1)?;
+                Ok(Command {
+                    command_raw: row.get(3)?,
+                    time: when_run.and_then(|timestamp| Local.timestamp_opt(timestamp, 0).single()),
+                    duration: when_run
+                        .zip(end_time)
+                        .and_then(|(start, end)| u64::try_from(end - start).ok())
+                        .map(Duration::from_secs),
+                    exit_code: row.get(2)?,
+                    cwd: row.get(4)?,
+                    session: row.get(5)?,
+                    ..Default::default()
+                })
+            })?
+            .collect::<Result<_, _>>()?;
+        Ok(commands)
+    }
+}
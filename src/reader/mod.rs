@@ -0,0 +1,8 @@
+pub mod atuin;
+pub mod fish;
+pub mod hishtory;
+pub mod histdb;
+pub mod ksh;
+pub mod mcfly;
+pub mod nu;
+pub mod xonsh;
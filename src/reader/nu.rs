@@ -0,0 +1,40 @@
+use chrono::{Local, TimeZone};
+use rusqlite::{Connection, OpenFlags};
+use std::{error::Error, path::Path, time::Duration};
+
+use crate::parser::Command;
+
+/// Reads the `history.sqlite3` written by nushell when
+/// `$env.config.history.file_format = "sqlite"`.
+pub struct NuReader;
+
+impl NuReader {
+    pub fn read(path: &Path) -> Result<Vec<Command>, Box<dyn Error>> {
+        let conn = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+        let mut stmt = conn.prepare(
+            "SELECT start_timestamp, duration_ms, exit_status, command_line, cwd, session_id, hostname
+            FROM history
+            ORDER BY start_timestamp",
+        )?;
+        let commands = stmt
+            .query_map([], |row| {
+                Ok(Command {
+                    command_raw: row.get(3)?,
+                    time: row
+                        .get::<_, Option<i64>>(0)?
+                        .and_then(|timestamp| Local.timestamp_millis_opt(timestamp).single()),
+                    duration: row
+                        .get::<_, Option<i64>>(1)?
+                        .and_then(|duration| u64::try_from(duration).ok())
+                        .map(Duration::from_millis),
+                    exit_code: row.get(2)?,
+                    cwd: row.get(4)?,
+                    session: row.get::<_, Option<i64>>(5)?.map(|id| id.to_string()),
+                    hostname: row.get(6)?,
+                    ..Default::default()
+                })
+            })?
+            .collect::<Result<_, _>>()?;
+        Ok(commands)
+    }
+}
@@ -0,0 +1,80 @@
+use chrono::{Local, TimeZone};
+use serde::Deserialize;
+use std::{error::Error, fs, path::Path, time::Duration};
+
+use crate::{error::ParseError, parser::Command, source::Entry};
+
+#[derive(Deserialize)]
+struct SessionFile {
+    data: Session,
+}
+
+#[derive(Deserialize)]
+struct Session {
+    #[serde(default)]
+    cmds: Vec<SessionCommand>,
+    sessionid: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct SessionCommand {
+    inp: String,
+    rtn: Option<i64>,
+    ts: Option<(f64, f64)>,
+}
+
+/// Reads the per-session JSON files xonsh writes to `history_json/`.
+pub struct XonshReader;
+
+impl XonshReader {
+    /// Reads a single session file, or every session file in a directory,
+    /// merged in chronological order. Session files of a directory that fail
+    /// to parse, as those left truncated by a crash, are added to `skipped`.
+    pub fn read(path: &Path, skipped: &mut Vec<Entry>) -> Result<Vec<Command>, Box<dyn Error>> {
+        if path.is_file() {
+            return Self::read_session(&fs::read(path)?);
+        }
+        let mut commands = vec![];
+        for entry in fs::read_dir(path)? {
+            let path = entry?.path();
+            if path.extension().is_some_and(|ext| ext == "json") {
+                let session = fs::read(&path)
+                    .map_err(Into::into)
+                    .and_then(|bytes| Self::read_session(&bytes));
+                match session {
+                    Ok(session) => commands.extend(session),
+                    Err(_) => skipped.push(Entry::Skipped {
+                        reason: ParseError::MalformedFile,
+                        raw: path
+                            .file_name()
+                            .unwrap_or_default()
+                            .to_string_lossy()
+                            .into(),
+                    }),
+                }
+            }
+        }
+        commands.sort_by_key(|command| command.time);
+        Ok(commands)
+    }
+
+    pub fn read_session(bytes: &[u8]) -> Result<Vec<Command>, Box<dyn Error>> {
+        let session = serde_json::from_slice::<SessionFile>(bytes)?.data;
+        Ok(session
+            .cmds
+            .into_iter()
+            .map(|cmd| Command {
+                command_raw: cmd.inp,
+                time: cmd.ts.and_then(|(start, _)| {
+                    Local.timestamp_millis_opt((start * 1000.0) as i64).single()
+                }),
+                duration: cmd
+                    .ts
+                    .and_then(|(start, end)| Duration::try_from_secs_f64(end - start).ok()),
+                exit_code: cmd.rtn,
+                session: session.sessionid.clone(),
+                ..Default::default()
+            })
+            .collect())
+    }
+}
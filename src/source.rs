@@ -0,0 +1,498 @@
+use std::{
+    io::{self, BufRead, BufReader, Read},
+    time::SystemTime,
+    vec,
+};
+
+use crate::{
+    config::PatternProvider,
+    error::{ParseError, WrappedError},
+    history::HistoryProvider,
+    parser::{Command, CommandParser},
+};
+
+/// A history entry, either a command record or the raw text of an entry that
+/// could not be parsed, kept for `--diagnose`.
+pub enum Entry {
+    Command(Box<Command>),
+    Skipped { reason: ParseError, raw: String },
+}
+
+/// A source of command records, read one after another.
+///
+/// Records only need `command_raw` and, when known, `time` and the other
+/// metadata; splitting command lines and finding the command names is left to
+/// [`CommandParser`]. Implement it to feed any kind of log into the merge and
+/// the stats.
+pub trait HistorySource {
+    /// Name of the shell or tool, reported as the shell of every command.
+    fn name(&self) -> String;
+
+    /// Reads the next entry, or `None` once the history is exhausted.
+    fn next_entry(&mut self) -> Result<Option<Entry>, WrappedError>;
+
+    /// When the history was last written, used to estimate the timestamps of
+    /// trailing untimed entries.
+    fn modified(&self) -> Option<SystemTime> {
+        None
+    }
+
+    /// Splits a command line into the commands it runs.
+    fn split(&self, command_line: &str) -> Vec<String> {
+        CommandParser::split(command_line)
+    }
+}
+
+/// Records read all at once, from a database or a structured history file.
+pub struct Records {
+    name: String,
+    records: vec::IntoIter<Command>,
+    skipped: vec::IntoIter<Entry>,
+    modified: Option<SystemTime>,
+}
+
+impl Records {
+    pub fn new(name: impl ToString, records: Vec<Command>) -> Self {
+        Records {
+            name: name.to_string(),
+            records: records.into_iter(),
+            skipped: vec![].into_iter(),
+            modified: None,
+        }
+    }
+
+    /// Adds the entries that could not be read, such as the files of a
+    /// history directory that failed to parse, for `--diagnose`.
+    pub fn skipped(mut self, skipped: Vec<Entry>) -> Self {
+        self.skipped = skipped.into_iter();
+        self
+    }
+
+    pub fn modified(mut self, modified: Option<SystemTime>) -> Self {
+        self.modified = modified;
+        self
+    }
+}
+
+impl HistorySource for Records {
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn next_entry(&mut self) -> Result<Option<Entry>, WrappedError> {
+        Ok(self.skipped.next().or_else(|| {
+            self.records
+                .next()
+                .map(|record| Entry::Command(Box::new(record)))
+        }))
+    }
+
+    fn modified(&self) -> Option<SystemTime> {
+        self.modified
+    }
+}
+
+/// A plain-text history file of one of the supported shells, read entry by
+/// entry.
+pub struct TextHistory {
+    provider: HistoryProvider,
+    buff_reader: BufReader<Box<dyn Read>>,
+    modified: Option<SystemTime>,
+    // the line read past the end of the previous entry
+    lookahead: Option<String>,
+}
+
+impl TextHistory {
+    pub fn new(provider: HistoryProvider, reader: Box<dyn Read>) -> Self {
+        TextHistory {
+            provider,
+            buff_reader: BufReader::new(reader),
+            modified: None,
+            lookahead: None,
+        }
+    }
+
+    pub fn modified(mut self, modified: Option<SystemTime>) -> Self {
+        self.modified = modified;
+        self
+    }
+
+    /// zsh writes every byte in 0x83..=0xa2 (and NUL) as 0x83 followed by the
+    /// byte xor 0x20, which breaks the UTF-8 of non-ASCII commands.
+    fn unmetafy(bytes: &[u8]) -> Vec<u8> {
+        const META: u8 = 0x83;
+        let mut res = Vec::with_capacity(bytes.len());
+        let mut iter = bytes.iter();
+        while let Some(&b) = iter.next() {
+            match b {
+                META => res.extend(iter.next().map(|&b| b ^ 0x20)),
+                _ => res.push(b),
+            }
+        }
+        res
+    }
+
+    fn next_raw(&mut self) -> io::Result<Option<String>> {
+        let buff_reader = &mut self.buff_reader;
+        Ok(match self.provider {
+            HistoryProvider::Zsh
+            | HistoryProvider::Atuin
+            | HistoryProvider::Fish
+            | HistoryProvider::Xonsh
+            | HistoryProvider::Histdb
+            | HistoryProvider::McFly
+            | HistoryProvider::Hishtory => {
+                let mut block = String::new();
+                let mut buf = vec![];
+                loop {
+                    buff_reader.read_until(b'\n', &mut buf)?;
+                    if buf.is_empty() {
+                        return Ok(if block.is_empty() { None } else { Some(block) });
+                    }
+                    let line = match self.provider {
+                        HistoryProvider::Zsh => Self::unmetafy(&buf),
+                        _ => buf.clone(),
+                    };
+                    let str = String::from_utf8_lossy(&line).trim_end().to_owned();
+                    block += &str;
+                    if str.is_empty() {
+                        buf.clear();
+                        continue;
+                    }
+                    // multi-line commands end every line but the last with a backslash
+                    if str.ends_with('\\') {
+                        block.pop();
+                        block.push('\n');
+                        buf.clear();
+                        continue;
+                    }
+                    break Some(block);
+                }
+            }
+            HistoryProvider::Nu | HistoryProvider::Ksh => {
+                let mut buf = vec![];
+                loop {
+                    buff_reader.read_until(b'\n', &mut buf)?;
+                    if buf.is_empty() {
+                        return Ok(None);
+                    }
+                    let str = String::from_utf8_lossy(&buf).trim_end().to_owned();
+                    if str.is_empty() {
+                        buf.clear();
+                        continue;
+                    }
+                    break Some(str);
+                }
+            }
+            // PSReadLine ends every line but the last of a multi-line command with a
+            // backtick, joined with a space so that continued parameters stay with
+            // their cmdlet
+            HistoryProvider::Pwsh => {
+                let mut block = String::new();
+                let mut buf = vec![];
+                loop {
+                    buf.clear();
+                    buff_reader.read_until(b'\n', &mut buf)?;
+                    if buf.is_empty() {
+                        return Ok(if block.is_empty() { None } else { Some(block) });
+                    }
+                    let str = String::from_utf8_lossy(&buf).trim_end().to_owned();
+                    match str.strip_suffix('`') {
+                        Some(line) => block = block + line.trim_end() + " ",
+                        None if block.is_empty() && str.is_empty() => continue,
+                        None => break Some(block + &str),
+                    }
+                }
+            }
+            // every tcsh entry starts with a `#+<epoch>` line
+            HistoryProvider::Tcsh => {
+                self.next_timestamped(|line| Self::is_timestamp(line.strip_prefix("#+")))?
+            }
+            HistoryProvider::Bash => {
+                self.next_timestamped(|line| Self::is_timestamp(line.strip_prefix('#')))?
+            }
+        })
+    }
+
+    /// Reads an entry that starts at a timestamp line and runs until the next
+    /// one, so that multi-line commands stay together. Lines outside of such
+    /// entries are entries of their own.
+    fn next_timestamped(&mut self, is_header: fn(&str) -> bool) -> io::Result<Option<String>> {
+        let read_line = |buff_reader: &mut BufReader<Box<dyn Read>>| -> io::Result<_> {
+            let mut buf = vec![];
+            buff_reader.read_until(b'\n', &mut buf)?;
+            Ok((!buf.is_empty()).then(|| String::from_utf8_lossy(&buf).into_owned()))
+        };
+        let mut block = match self.lookahead.take() {
+            Some(line) => line,
+            None => match read_line(&mut self.buff_reader)? {
+                Some(line) => line,
+                None => return Ok(None),
+            },
+        };
+        if !is_header(&block) {
+            return Ok(Some(block));
+        }
+        while let Some(line) = read_line(&mut self.buff_reader)? {
+            if is_header(&line) {
+                self.lookahead = Some(line);
+                break;
+            }
+            block += &line;
+        }
+        Ok(Some(block))
+    }
+
+    fn is_timestamp(s: Option<&str>) -> bool {
+        s.map(str::trim_end)
+            .is_some_and(|s| !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit()))
+    }
+}
+
+impl HistorySource for TextHistory {
+    fn name(&self) -> String {
+        self.provider.to_string()
+    }
+
+    fn next_entry(&mut self) -> Result<Option<Entry>, WrappedError> {
+        let Some(raw) = self.next_raw()? else {
+            return Ok(None);
+        };
+        Ok(Some(
+            match CommandParser::from_raw(raw.clone()).read(&self.provider) {
+                Ok(command) => Entry::Command(Box::new(command)),
+                Err(reason) => Entry::Skipped { reason, raw },
+            },
+        ))
+    }
+
+    fn modified(&self) -> Option<SystemTime> {
+        self.modified
+    }
+
+    fn split(&self, command_line: &str) -> Vec<String> {
+        match self.provider {
+            HistoryProvider::Pwsh => CommandParser::split_pwsh(command_line),
+            _ => CommandParser::split(command_line),
+        }
+    }
+}
+
+/// A log in a format declared in the config file, split into records by the
+/// delimiter of the provider.
+pub struct PatternHistory {
+    provider: PatternProvider,
+    buff_reader: BufReader<Box<dyn Read>>,
+    modified: Option<SystemTime>,
+}
+
+impl PatternHistory {
+    pub fn new(provider: PatternProvider, reader: Box<dyn Read>) -> Self {
+        PatternHistory {
+            provider,
+            buff_reader: BufReader::new(reader),
+            modified: None,
+        }
+    }
+
+    pub fn modified(mut self, modified: Option<SystemTime>) -> Self {
+        self.modified = modified;
+        self
+    }
+
+    fn next_raw(&mut self) -> io::Result<Option<String>> {
+        let delimiter = self.provider.delimiter.as_bytes();
+        let last = delimiter[delimiter.len() - 1];
+        let mut buf = vec![];
+        loop {
+            if self.buff_reader.read_until(last, &mut buf)? == 0 {
+                break;
+            }
+            if let Some(record) = buf.strip_suffix(delimiter) {
+                if record.iter().all(u8::is_ascii_whitespace) {
+                    buf.clear();
+                    continue;
+                }
+                return Ok(Some(String::from_utf8_lossy(record).into_owned()));
+            }
+        }
+        // the last record ends with the newline of the file, not the delimiter
+        let record = buf.trim_ascii_end();
+        Ok((!record.is_empty()).then(|| String::from_utf8_lossy(record).into_owned()))
+    }
+}
+
+impl HistorySource for PatternHistory {
+    fn name(&self) -> String {
+        self.provider.name.clone()
+    }
+
+    fn next_entry(&mut self) -> Result<Option<Entry>, WrappedError> {
+        let Some(raw) = self.next_raw()? else {
+            return Ok(None);
+        };
+        Ok(Some(
+            match CommandParser::from_raw(raw.clone()).read_pattern(&self.provider) {
+                Ok(command) => Entry::Command(Box::new(command)),
+                Err(reason) => Entry::Skipped { reason, raw },
+            },
+        ))
+    }
+
+    fn modified(&self) -> Option<SystemTime> {
+        self.modified
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Local, TimeZone};
+    use regex::Regex;
+    use std::io::Cursor;
+
+    fn text_history(provider: HistoryProvider, content: &[u8]) -> TextHistory {
+        TextHistory::new(provider, Box::new(Cursor::new(content.to_vec())))
+    }
+
+    fn pattern_history(
+        delimiter: &str,
+        pattern: &str,
+        time_format: Option<&str>,
+        content: &str,
+    ) -> PatternHistory {
+        let provider = PatternProvider {
+            name: "log".into(),
+            file: None,
+            delimiter: delimiter.into(),
+            pattern: Regex::new(pattern).unwrap(),
+            time_format: time_format.map(String::from),
+        };
+        PatternHistory::new(provider, Box::new(Cursor::new(content.as_bytes().to_vec())))
+    }
+
+    fn records(history: &mut dyn HistorySource) -> Vec<Command> {
+        let mut records = vec![];
+        while let Some(entry) = history.next_entry().unwrap() {
+            match entry {
+                Entry::Command(record) => records.push(*record),
+                Entry::Skipped { reason, raw } => panic!("skipped {:?}: {}", raw, reason),
+            }
+        }
+        records
+    }
+
+    fn commands(history: &mut TextHistory) -> Vec<String> {
+        let mut commands = vec![];
+        while let Some(raw) = history.next_raw().unwrap() {
+            commands.extend(history.split(&raw));
+        }
+        commands
+    }
+
+    #[test]
+    fn pwsh_continuations() {
+        let mut history = text_history(
+            HistoryProvider::Pwsh,
+            b"Get-ChildItem `\n-Recurse `\n-Force\nWrite-Host `\n\"hi\"\nls\n",
+        );
+        assert_eq!(
+            commands(&mut history),
+            ["get-childitem -Recurse -Force", "write-host \"hi\"", "ls"]
+        );
+    }
+
+    #[test]
+    fn pattern_custom_delimiter() {
+        let mut history = pattern_history(
+            "\n--\n",
+            r"(?s)^\[(?P<time>[^\]]+)\] (?P<command>.+)$",
+            Some("%Y-%m-%d %H:%M:%S"),
+            "[2024-03-01 10:00:00] make\n--\n[2024-03-01 10:05:00] for f in *\ndo ls\ndone\n",
+        );
+        let records = records(&mut history);
+        let commands: Vec<_> = records.iter().map(|record| &record.command_raw).collect();
+        assert_eq!(commands, ["make", "for f in *\ndo ls\ndone"]);
+        assert_eq!(
+            records[1].time,
+            Local.with_ymd_and_hms(2024, 3, 1, 10, 5, 0).single()
+        );
+    }
+
+    #[test]
+    fn pattern_unix_timestamps() {
+        let mut history = pattern_history(
+            "\n",
+            r"^(?P<time>\d+) (?P<command>.+)$",
+            None,
+            "1700000000 ls\r\n\n1700000060 pwd\n",
+        );
+        let records = records(&mut history);
+        let commands: Vec<_> = records.iter().map(|record| &record.command_raw).collect();
+        assert_eq!(commands, ["ls", "pwd"]);
+        assert_eq!(records[1].time, Local.timestamp_opt(1700000060, 0).single());
+    }
+
+    #[test]
+    fn bash_timestamped_entries() {
+        let mut history = text_history(
+            HistoryProvider::Bash,
+            b"# stray comment\nls\n\
+              #1700000000\nfor f in *; do\n  echo $f\ndone\n# inner comment\n\
+              #1700000005\n\n\
+              #1700000010\nmake\n",
+        );
+        let records = records(&mut history);
+        let entries: Vec<_> = records
+            .iter()
+            .map(|record| (record.command_raw.trim(), record.time))
+            .collect();
+        let at = |secs| Local.timestamp_opt(secs, 0).single();
+        assert_eq!(
+            entries,
+            [
+                ("# stray comment", None),
+                ("ls", None),
+                (
+                    "for f in *; do\n  echo $f\ndone\n# inner comment",
+                    at(1700000000)
+                ),
+                ("", at(1700000005)),
+                ("make", at(1700000010)),
+            ]
+        );
+        assert_eq!(history.split(entries[2].0), ["echo $f"]);
+    }
+
+    // the inverse of `TextHistory::unmetafy`, as zsh writes its history
+    fn metafy(s: &str) -> Vec<u8> {
+        let mut bytes = vec![];
+        for &b in s.as_bytes() {
+            match b {
+                0 | 0x83..=0xa2 => bytes.extend([0x83, b ^ 0x20]),
+                _ => bytes.push(b),
+            }
+        }
+        bytes
+    }
+
+    #[test]
+    fn zsh_metafied_entries() {
+        let lines = [
+            ": 1700000000:0;echo 你好世界 🎉",
+            ": 1700000005:0;cd ~/Документы",
+        ];
+        let mut content = vec![];
+        for line in lines {
+            content.extend(metafy(line));
+            content.push(b'\n');
+        }
+        assert!(content.contains(&0x83));
+        let mut history = text_history(HistoryProvider::Zsh, &content);
+        let commands: Vec<_> = records(&mut history)
+            .into_iter()
+            .map(|record| record.command_raw)
+            .collect();
+        assert_eq!(commands, ["echo 你好世界 🎉", "cd ~/Документы"]);
+    }
+}
@@ -1,6 +1,9 @@
 use chrono::{DateTime, Datelike, Duration, Local, NaiveDate, Timelike};
 use num_traits::cast::FromPrimitive;
-use std::collections::HashMap;
+use std::{
+    collections::{HashMap, HashSet},
+    time,
+};
 
 use crate::{
     parser::Command,
@@ -10,6 +13,7 @@ use crate::{
 #[derive(Default)]
 pub struct Statistic {
     year: i32,
+    plot_estimated: bool,
 
     list_daytime: Vec<usize>,
     list_daytime_today: Vec<usize>,
@@ -22,13 +26,36 @@ pub struct Statistic {
     map_command_daily: HashMap<String, usize>,
     map_command_monthly: Vec<HashMap<String, usize>>,
     map_command_annual: HashMap<String, usize>,
+    map_typed_annual: HashMap<String, usize>,
+    map_alias: HashMap<String, String>,
+    map_subcommand: HashMap<String, HashMap<String, usize>>,
+    map_flag: HashMap<String, HashMap<String, usize>>,
+    map_usage: HashMap<String, usize>,
+    map_shell: HashMap<String, usize>,
+    map_wrapper: HashMap<String, usize>,
 
     today_command_count: usize,
     command_count: usize,
     command_count_total: usize,
+    untimed_count: usize,
+    estimated_count: usize,
+    map_command_untimed: HashMap<String, usize>,
 
     first_command: String,
     first_command_time: DateTime<Local>,
+
+    exit_known_count: usize,
+    failed_count: usize,
+    map_command_exit_known: HashMap<String, usize>,
+    map_command_failed: HashMap<String, usize>,
+
+    duration_total: time::Duration,
+    map_command_duration: HashMap<String, time::Duration>,
+
+    map_cwd: HashMap<String, usize>,
+    map_path: HashMap<String, usize>,
+    map_hostname: HashMap<String, usize>,
+    sessions: HashSet<String>,
 }
 
 impl Statistic {
@@ -47,6 +74,12 @@ impl Statistic {
         }
     }
 
+    /// Whether commands with estimated timestamps show up in the daily and
+    /// hourly graphs. They always count towards the totals and rankings.
+    pub fn plot_estimated(&mut self, plot_estimated: bool) {
+        self.plot_estimated = plot_estimated;
+    }
+
     pub fn analyze(&mut self, c: &Command) {
         self.map_command_total
             .entry(c.command.clone())
@@ -69,11 +102,17 @@ impl Statistic {
             let weekday = time.weekday() as usize;
             let day = time.ordinal0() as usize;
 
-            self.list_daytime[hour] += 1;
+            let plot = !c.time_estimated || self.plot_estimated;
+            if plot {
+                self.list_daytime[hour] += 1;
+                self.list_day[day] += 1;
+            }
             self.list_weekday[weekday] += 1;
             self.list_month[month] += 1;
-            self.list_day[day] += 1;
             self.command_count += 1;
+            if c.time_estimated {
+                self.estimated_count += 1;
+            }
 
             if self.first_command_time > time {
                 self.first_command.clone_from(&c.command_raw);
@@ -84,21 +123,317 @@ impl Statistic {
                 .entry(c.command.clone())
                 .and_modify(|counter| *counter += 1)
                 .or_insert(1);
+            let typed = c.alias.as_ref().unwrap_or(&c.command);
+            self.map_typed_annual
+                .entry(typed.clone())
+                .and_modify(|counter| *counter += 1)
+                .or_insert(1);
+            if let Some(alias) = &c.alias {
+                self.map_alias.insert(alias.clone(), c.command.clone());
+            }
+            if let Some(subcommand) = &c.subcommand {
+                self.map_subcommand
+                    .entry(c.command.clone())
+                    .or_default()
+                    .entry(subcommand.clone())
+                    .and_modify(|counter| *counter += 1)
+                    .or_insert(1);
+            }
+            // options are counted per subcommand, as for `git commit --amend`
+            let command = match &c.subcommand {
+                Some(subcommand) => format!("{} {}", c.command, subcommand),
+                None => c.command.clone(),
+            };
+            for flag in &c.flags {
+                self.map_flag
+                    .entry(command.clone())
+                    .or_default()
+                    .entry(flag.clone())
+                    .and_modify(|counter| *counter += 1)
+                    .or_insert(1);
+            }
+            self.map_usage
+                .entry(command)
+                .and_modify(|counter| *counter += 1)
+                .or_insert(1);
             self.map_command_monthly[month]
                 .entry(c.command.clone())
                 .and_modify(|counter| *counter += 1)
                 .or_insert(1);
+            self.map_shell
+                .entry(c.shell.clone())
+                .and_modify(|counter| *counter += 1)
+                .or_insert(1);
+            for wrapper in &c.wrappers {
+                self.map_wrapper
+                    .entry(wrapper.clone())
+                    .and_modify(|counter| *counter += 1)
+                    .or_insert(1);
+            }
 
             let delta = if now.hour() < 6 { 18 } else { -6 };
             if self.year == 0 && (time + Duration::hours(delta)).ordinal0() == now.ordinal0() {
                 self.today_command_count += 1;
-                self.list_daytime_today[hour] += 1;
+                if plot {
+                    self.list_daytime_today[hour] += 1;
+                }
                 self.map_command_daily
                     .entry(c.command.clone())
                     .and_modify(|counter| *counter += 1)
                     .or_insert(1);
             }
+
+            self.analyze_context(c);
+        } else {
+            self.untimed_count += 1;
+            self.map_command_untimed
+                .entry(c.command.clone())
+                .and_modify(|counter| *counter += 1)
+                .or_insert(1);
+        }
+    }
+
+    fn analyze_context(&mut self, c: &Command) {
+        if let Some(exit_code) = c.exit_code {
+            self.exit_known_count += 1;
+            self.map_command_exit_known
+                .entry(c.command.clone())
+                .and_modify(|counter| *counter += 1)
+                .or_insert(1);
+            if exit_code != 0 {
+                self.failed_count += 1;
+                self.map_command_failed
+                    .entry(c.command.clone())
+                    .and_modify(|counter| *counter += 1)
+                    .or_insert(1);
+            }
+        }
+        if let Some(duration) = c.duration {
+            self.duration_total += duration;
+            self.map_command_duration
+                .entry(c.command.clone())
+                .and_modify(|total| *total += duration)
+                .or_insert(duration);
         }
+        if let Some(cwd) = &c.cwd {
+            self.map_cwd
+                .entry(cwd.clone())
+                .and_modify(|counter| *counter += 1)
+                .or_insert(1);
+        }
+        for path in &c.paths {
+            self.map_path
+                .entry(path.clone())
+                .and_modify(|counter| *counter += 1)
+                .or_insert(1);
+        }
+        if let Some(hostname) = &c.hostname {
+            self.map_hostname
+                .entry(hostname.clone())
+                .and_modify(|counter| *counter += 1)
+                .or_insert(1);
+        }
+        if let Some(session) = &c.session {
+            self.sessions.insert(session.clone());
+        }
+    }
+
+    /// The year analyzed, or 0 for the recent stats of the current year.
+    pub fn year(&self) -> i32 {
+        self.year
+    }
+
+    /// Commands run in the analyzed year.
+    pub fn command_count(&self) -> usize {
+        self.command_count
+    }
+
+    /// Commands in the whole history, timed or not.
+    pub fn total_command_count(&self) -> usize {
+        self.command_count_total
+    }
+
+    pub fn today_command_count(&self) -> usize {
+        self.today_command_count
+    }
+
+    pub fn untimed_count(&self) -> usize {
+        self.untimed_count
+    }
+
+    /// Commands of the analyzed year whose timestamps were estimated.
+    pub fn estimated_count(&self) -> usize {
+        self.estimated_count
+    }
+
+    /// The earliest command of the analyzed year and when it was run.
+    pub fn first_command(&self) -> Option<(&str, DateTime<Local>)> {
+        (self.command_count > 0).then_some((self.first_command.as_str(), self.first_command_time))
+    }
+
+    /// Commands per day of the year, starting on January 1st.
+    pub fn daily(&self) -> &[usize] {
+        &self.list_day
+    }
+
+    /// Commands per hour of the day.
+    pub fn hourly(&self) -> &[usize] {
+        &self.list_daytime
+    }
+
+    pub fn hourly_today(&self) -> &[usize] {
+        &self.list_daytime_today
+    }
+
+    /// Commands per weekday, starting on Monday.
+    pub fn weekdays(&self) -> &[usize] {
+        &self.list_weekday
+    }
+
+    /// Commands per month of the analyzed year.
+    pub fn monthly(&self) -> &[usize] {
+        &self.list_month
+    }
+
+    /// Commands per month over all the years in history.
+    pub fn monthly_total(&self) -> &[usize] {
+        &self.list_month_total
+    }
+
+    /// Commands of the analyzed year, most used first.
+    pub fn commands(&self) -> Vec<(String, usize)> {
+        Self::rank(&self.map_command_annual)
+    }
+
+    /// Commands of the analyzed year as typed, with aliases and shell
+    /// functions unexpanded.
+    pub fn typed_commands(&self) -> Vec<(String, usize)> {
+        Self::rank(&self.map_typed_annual)
+    }
+
+    /// The program an alias or shell function ran, when it was used in the
+    /// analyzed year.
+    pub fn alias_expansion(&self, alias: &str) -> Option<&str> {
+        self.map_alias.get(alias).map(String::as_str)
+    }
+
+    /// Subcommands of the analyzed year, as `git commit`, most used first.
+    pub fn subcommands(&self) -> Vec<(String, usize)> {
+        let subcommands = self
+            .map_subcommand
+            .iter()
+            .flat_map(|(tool, subcommands)| {
+                subcommands
+                    .iter()
+                    .map(move |(subcommand, &count)| (format!("{} {}", tool, subcommand), count))
+            })
+            .collect();
+        Self::rank(&subcommands)
+    }
+
+    /// Subcommands of a tool in the analyzed year, most used first.
+    pub fn subcommands_of(&self, tool: &str) -> Vec<(String, usize)> {
+        self.map_subcommand
+            .get(tool)
+            .map(Self::rank)
+            .unwrap_or_default()
+    }
+
+    /// Options of the analyzed year, as `ls -l`, most used first.
+    pub fn flags(&self) -> Vec<(String, usize)> {
+        let flags = self
+            .map_flag
+            .iter()
+            .flat_map(|(command, flags)| {
+                flags
+                    .iter()
+                    .map(move |(flag, &count)| (format!("{} {}", command, flag), count))
+            })
+            .collect();
+        Self::rank(&flags)
+    }
+
+    /// Options of a command, or of a subcommand as `git commit`, in the
+    /// analyzed year, most used first.
+    pub fn flags_of(&self, command: &str) -> Vec<(String, usize)> {
+        self.map_flag
+            .get(command)
+            .map(Self::rank)
+            .unwrap_or_default()
+    }
+
+    /// Commands of the whole history, most used first.
+    pub fn commands_total(&self) -> Vec<(String, usize)> {
+        Self::rank(&self.map_command_total)
+    }
+
+    pub fn commands_today(&self) -> Vec<(String, usize)> {
+        Self::rank(&self.map_command_daily)
+    }
+
+    /// Commands of a month of the analyzed year, `month0` starting at 0.
+    pub fn commands_in_month(&self, month0: usize) -> Vec<(String, usize)> {
+        self.map_command_monthly
+            .get(month0)
+            .map(Self::rank)
+            .unwrap_or_default()
+    }
+
+    pub fn untimed_commands(&self) -> Vec<(String, usize)> {
+        Self::rank(&self.map_command_untimed)
+    }
+
+    /// Commands of the analyzed year per shell or history tool.
+    pub fn shells(&self) -> Vec<(String, usize)> {
+        Self::rank(&self.map_shell)
+    }
+
+    /// Commands of the analyzed year run through each wrapper, such as `sudo`.
+    pub fn wrappers(&self) -> Vec<(String, usize)> {
+        Self::rank(&self.map_wrapper)
+    }
+
+    /// Commands with a known exit status, and how many of them failed.
+    pub fn exit_counts(&self) -> (usize, usize) {
+        (self.exit_known_count, self.failed_count)
+    }
+
+    pub fn failed_commands(&self) -> Vec<(String, usize)> {
+        Self::rank(&self.map_command_failed)
+    }
+
+    pub fn time_spent(&self) -> time::Duration {
+        self.duration_total
+    }
+
+    pub fn command_durations(&self) -> Vec<(String, time::Duration)> {
+        Self::rank(&self.map_command_duration)
+    }
+
+    pub fn directories(&self) -> Vec<(String, usize)> {
+        Self::rank(&self.map_cwd)
+    }
+
+    pub fn paths(&self) -> Vec<(String, usize)> {
+        Self::rank(&self.map_path)
+    }
+
+    pub fn hostnames(&self) -> Vec<(String, usize)> {
+        Self::rank(&self.map_hostname)
+    }
+
+    pub fn session_count(&self) -> usize {
+        self.sessions.len()
+    }
+
+    // highest first, ties in name order so that rankings are stable
+    fn rank<T: Ord + Copy>(map: &HashMap<String, T>) -> Vec<(String, T)> {
+        let mut ranking: Vec<_> = map
+            .iter()
+            .map(|(key, &value)| (key.clone(), value))
+            .collect();
+        ranking.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        ranking
     }
 
     pub fn most_active_period(&self) -> &str {
@@ -168,6 +503,18 @@ impl Statistic {
             View::style_keyword(self.command_count),
             View::style_keyword(self.command_count_total)
         ));
+        if self.estimated_count > 0 {
+            View::content(&format!(
+                "- The timestamps of {} of them were estimated from the commands around them.\n",
+                View::style_keyword(self.estimated_count),
+            ));
+        }
+        if self.untimed_count > 0 {
+            View::content(&format!(
+                "- {} commands in history carry no timestamp, they are listed under Untimed Commands.\n",
+                View::style_keyword(self.untimed_count),
+            ));
+        }
 
         let (day, max) = self
             .list_day
@@ -251,6 +598,18 @@ impl Statistic {
             );
         }
 
+        // Only shown when aliases were expanded
+        if !self.map_alias.is_empty() {
+            View::sub_title("Favorite Commands, As Typed");
+
+            for (typed, count) in self.typed_commands().iter().take(10) {
+                match self.alias_expansion(typed) {
+                    Some(command) => View::display_item(&format!("{} → {}", typed, command), count),
+                    None => View::display_item(typed, count),
+                }
+            }
+        }
+
         View::sub_title("Also Frequently Used");
 
         for (command, &count) in fav_command.iter().skip(10).take(15) {
@@ -263,10 +622,224 @@ impl Statistic {
         View::content("...");
         View::wait();
 
+        self.output_subcommands();
+        self.output_flags();
+        self.output_untimed();
+        self.output_shell_share();
+        self.output_wrapper_share();
+        self.output_context();
+
         View::hint_finish(self.year);
         View::wait();
     }
 
+    // Only shown when tools with subcommands were used, with a drill-down into
+    // the three most used ones
+    fn output_subcommands(&self) {
+        if self.map_subcommand.is_empty() {
+            return;
+        }
+        View::sub_title("Favorite Subcommands");
+
+        for (subcommand, count) in self.subcommands().iter().take(10) {
+            View::display_item(subcommand, count);
+        }
+        View::wait();
+
+        let mut tools: Vec<_> = self
+            .map_subcommand
+            .keys()
+            .map(|tool| {
+                (
+                    tool,
+                    self.map_command_annual
+                        .get(tool)
+                        .copied()
+                        .unwrap_or_default(),
+                )
+            })
+            .collect();
+        tools.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+        for (tool, total) in tools.into_iter().take(3) {
+            let subcommands = self.subcommands_of(tool);
+            View::sub_title_with_keyword(&format!("{} Subcommands", tool), subcommands.len());
+
+            let max = subcommands.first().map_or(0, |(_, count)| *count);
+            for (subcommand, count) in subcommands.iter().take(8) {
+                View::histogram_with_percentage(subcommand, *count, total, max);
+            }
+            View::wait();
+        }
+    }
+
+    // Only shown when options were used, with a drill-down into the three
+    // commands using them the most
+    fn output_flags(&self) {
+        let flags = self.flags();
+        if flags.is_empty() {
+            return;
+        }
+        View::sub_title("Favorite Options");
+
+        for (flag, count) in flags.iter().take(10) {
+            let command = flag.rsplit_once(' ').map_or("", |(command, _)| command);
+            let total = self.map_usage.get(command).copied().unwrap_or_default();
+            View::display_count_and_total(flag, *count, total);
+        }
+        View::wait();
+
+        let mut commands: Vec<_> = self
+            .map_flag
+            .iter()
+            .map(|(command, flags)| (command, flags.values().sum::<usize>()))
+            .collect();
+        commands.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+        for (command, _) in commands.into_iter().take(3) {
+            let flags = self.flags_of(command);
+            let total = self.map_usage.get(command).copied().unwrap_or_default();
+            View::sub_title_with_keyword(&format!("{} Options", command), flags.len());
+
+            let max = flags.first().map_or(0, |(_, count)| *count);
+            for (flag, count) in flags.iter().take(8) {
+                View::histogram_with_percentage(flag, *count, total, max);
+            }
+            View::wait();
+        }
+    }
+
+    // Only shown when part of the history has no timestamps
+    fn output_untimed(&self) {
+        if self.untimed_count == 0 {
+            return;
+        }
+        View::sub_title_with_keyword("Untimed Commands", self.untimed_count);
+
+        let mut untimed_commands: Vec<_> = self.map_command_untimed.iter().collect();
+        untimed_commands.sort_by(|a, b| b.1.cmp(a.1));
+        for (command, &count) in untimed_commands.iter().take(10) {
+            View::display_count_and_total(
+                command,
+                count,
+                *self.map_command_total.get(*command).unwrap(),
+            );
+        }
+        View::wait();
+    }
+
+    // Only shown when several history sources are merged
+    fn output_shell_share(&self) {
+        if self.map_shell.len() < 2 {
+            return;
+        }
+        let mut shells: Vec<_> = self.map_shell.iter().collect();
+        shells.sort_by(|a, b| b.1.cmp(a.1));
+
+        View::sub_title_with_keyword("Shell Share", shells[0].0);
+
+        let max = *shells[0].1;
+        for (shell, &count) in shells {
+            View::histogram_with_percentage(shell, count, self.command_count, max);
+        }
+        View::wait();
+    }
+
+    // Only shown when some commands were run through sudo, nohup and the like
+    fn output_wrapper_share(&self) {
+        let wrappers = self.wrappers();
+        let Some((_, max)) = wrappers.first() else {
+            return;
+        };
+
+        View::sub_title_with_keyword(
+            "Sudo Share",
+            format!(
+                "{:.1}%",
+                self.map_wrapper.get("sudo").copied().unwrap_or_default() as f64 * 100.0
+                    / self.command_count as f64
+            ),
+        );
+
+        for (wrapper, count) in wrappers.iter().take(10) {
+            View::histogram_with_percentage(wrapper, *count, self.command_count, *max);
+        }
+        View::wait();
+    }
+
+    // Only shown for history sources that record more than timestamps
+    fn output_context(&self) {
+        if self.exit_known_count > 0 {
+            View::sub_title_with_keyword(
+                "Failure Rate",
+                format!(
+                    "{:.1}%",
+                    self.failed_count as f64 * 100.0 / self.exit_known_count as f64
+                ),
+            );
+
+            let mut failed_commands: Vec<_> = self.map_command_failed.iter().collect();
+            failed_commands.sort_by(|a, b| b.1.cmp(a.1));
+            for (command, &count) in failed_commands.iter().take(10) {
+                View::display_count_and_total(
+                    command,
+                    count,
+                    *self.map_command_exit_known.get(*command).unwrap(),
+                );
+            }
+            View::wait();
+        }
+
+        if !self.map_command_duration.is_empty() {
+            View::sub_title_with_keyword("Time Spent", View::format_duration(self.duration_total));
+
+            let mut slow_commands: Vec<_> = self.map_command_duration.iter().collect();
+            slow_commands.sort_by(|a, b| b.1.cmp(a.1));
+            for (command, &duration) in slow_commands.iter().take(10) {
+                View::display_item(command, View::format_duration(duration));
+            }
+            View::wait();
+        }
+
+        if !self.map_cwd.is_empty() {
+            View::sub_title("Top Directories");
+
+            let mut directories: Vec<_> = self.map_cwd.iter().collect();
+            directories.sort_by(|a, b| b.1.cmp(a.1));
+            for (directory, &count) in directories.iter().take(10) {
+                View::display_item(directory, count);
+            }
+            View::wait();
+        }
+
+        if !self.map_path.is_empty() {
+            View::sub_title("Top Paths");
+
+            let mut paths: Vec<_> = self.map_path.iter().collect();
+            paths.sort_by(|a, b| b.1.cmp(a.1));
+            for (path, &count) in paths.iter().take(10) {
+                View::display_item(path, count);
+            }
+            View::wait();
+        }
+
+        if !self.map_hostname.is_empty() {
+            View::sub_title_with_keyword("Machines", self.map_hostname.len());
+
+            let mut hostnames: Vec<_> = self.map_hostname.iter().collect();
+            hostnames.sort_by(|a, b| b.1.cmp(a.1));
+            for (hostname, &count) in hostnames.iter().take(10) {
+                View::display_item(hostname, count);
+            }
+            if !self.sessions.is_empty() {
+                View::line_break();
+                View::content(&format!(
+                    "- Spread over {} shell sessions.",
+                    View::style_keyword(self.sessions.len())
+                ));
+            }
+            View::wait();
+        }
+    }
+
     pub fn output_recent(&self) {
         let mut component = Component::new(61, 6, View::display);
         component.edge();
@@ -309,6 +882,13 @@ impl Statistic {
             self.command_count,
             self.map_command_annual.len()
         ));
+        if self.untimed_count > 0 {
+            component.content(&format!(
+                "Untimed - {} commands / {} unique commands",
+                self.untimed_count,
+                self.map_command_untimed.len()
+            ));
+        }
         component.break_line();
 
         component.graph2(&self.list_day);
@@ -0,0 +1,145 @@
+use serde::Deserialize;
+use std::collections::HashMap;
+
+const GIT_OPTIONS: [&str; 8] = [
+    "-C",
+    "-c",
+    "--git-dir",
+    "--work-tree",
+    "--namespace",
+    "--super-prefix",
+    "--config-env",
+    "--list-cmds",
+];
+const DOCKER_OPTIONS: [&str; 10] = [
+    "-H",
+    "--host",
+    "-c",
+    "--context",
+    "--config",
+    "-l",
+    "--log-level",
+    "--tlscacert",
+    "--tlscert",
+    "--tlskey",
+];
+const KUBECTL_OPTIONS: [&str; 12] = [
+    "-n",
+    "--namespace",
+    "--context",
+    "--cluster",
+    "--kubeconfig",
+    "-s",
+    "--server",
+    "--user",
+    "--token",
+    "--as",
+    "--as-group",
+    "--request-timeout",
+];
+
+/// A program whose first operand is a subcommand, as the `commit` of
+/// `git commit`.
+///
+/// ```toml
+/// [tools.mytool]
+/// options = ["--profile"]
+/// ```
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Tool {
+    /// Global options that take the next argument as their value, as the `-C`
+    /// of `git -C dir status`. Other arguments starting with `-` are read as
+    /// flags.
+    #[serde(default)]
+    pub options: Vec<String>,
+}
+
+impl Tool {
+    fn new(options: &[&str]) -> Self {
+        Tool {
+            options: options.iter().map(|option| option.to_string()).collect(),
+        }
+    }
+}
+
+/// The table of programs with subcommands, the built-in ones and those
+/// declared in the config file.
+#[derive(Debug, Clone)]
+pub struct Tools(HashMap<String, Tool>);
+
+impl Default for Tools {
+    fn default() -> Self {
+        let tools = [
+            ("git", Tool::new(&GIT_OPTIONS)),
+            ("cargo", Tool::new(&["-Z", "-C", "--config", "--color"])),
+            ("rustup", Tool::new(&[])),
+            ("go", Tool::new(&[])),
+            ("docker", Tool::new(&DOCKER_OPTIONS)),
+            ("podman", Tool::new(&DOCKER_OPTIONS)),
+            ("kubectl", Tool::new(&KUBECTL_OPTIONS)),
+            (
+                "helm",
+                Tool::new(&["-n", "--namespace", "--kube-context", "--kubeconfig"]),
+            ),
+            ("npm", Tool::new(&["--prefix", "-w", "--workspace"])),
+            ("yarn", Tool::new(&["--cwd"])),
+            ("pnpm", Tool::new(&["-C", "--dir", "-F", "--filter"])),
+            ("pip", Tool::new(&[])),
+            ("pip3", Tool::new(&[])),
+            ("uv", Tool::new(&["--directory", "--project"])),
+            ("poetry", Tool::new(&["-C", "--directory"])),
+            ("conda", Tool::new(&[])),
+            ("apt", Tool::new(&["-o", "-c", "-t"])),
+            ("apt-get", Tool::new(&["-o", "-c", "-t"])),
+            ("dnf", Tool::new(&["-c", "--releasever"])),
+            ("brew", Tool::new(&[])),
+            ("nix", Tool::new(&["--option"])),
+            ("systemctl", Tool::new(&["-H", "--host", "-M", "--machine"])),
+            ("gh", Tool::new(&["-R", "--repo"])),
+            ("terraform", Tool::new(&[])),
+            (
+                "aws",
+                Tool::new(&["--profile", "--region", "--output", "--endpoint-url"]),
+            ),
+            (
+                "gcloud",
+                Tool::new(&["--project", "--account", "--configuration"]),
+            ),
+            ("tmux", Tool::new(&["-L", "-S", "-f"])),
+            ("dotnet", Tool::new(&[])),
+        ];
+        Tools(
+            tools
+                .into_iter()
+                .map(|(name, tool)| (name.to_string(), tool))
+                .collect(),
+        )
+    }
+}
+
+impl Tools {
+    /// Adds a tool, replacing the built-in one of the same name.
+    pub fn insert(&mut self, name: String, tool: Tool) {
+        self.0.insert(name, tool);
+    }
+
+    /// The subcommand of an argument vector starting with the program, after
+    /// its global options, or `None` for programs without subcommands.
+    pub fn subcommand(&self, args: &[String]) -> Option<String> {
+        let (program, args) = args.split_first()?;
+        let tool = self.0.get(program.rsplit('/').next()?)?;
+        let mut iter = args.iter();
+        while let Some(arg) = iter.next() {
+            match arg.as_str() {
+                arg if tool.options.iter().any(|option| option == arg) => {
+                    iter.next();
+                }
+                // the toolchain of `cargo +nightly build`
+                arg if arg.starts_with('-') || arg.starts_with('+') => {}
+                _ => return Some(arg.clone()),
+            }
+        }
+        None
+    }
+}
@@ -1,6 +1,7 @@
 use colored::*;
 use std::{
-    io::{stdout, Write},
+    fs::File,
+    io::{stdin, stdout, BufRead, BufReader, Write},
     thread::sleep,
     time::Duration,
 };
@@ -107,9 +108,12 @@ impl View {
     pub fn wait() {
         std::io::stdout().flush().unwrap();
         let mut input = String::new();
-        std::io::stdin()
-            .read_line(&mut input)
-            .expect("Failed to read line");
+        // stdin may be the history piped in, the terminal is read instead
+        match File::open("/dev/tty") {
+            Ok(tty) => BufReader::new(tty).read_line(&mut input),
+            Err(_) => stdin().read_line(&mut input),
+        }
+        .expect("Failed to read line");
         Self::clear();
     }
 
@@ -211,6 +215,21 @@ impl View {
         ));
     }
 
+    pub fn histogram_with_percentage<T: ToString>(
+        index: T,
+        count: usize,
+        total: usize,
+        max: usize,
+    ) {
+        Self::typewriter_for_line(&format!(
+            "{:<8} {}| {} {}",
+            index.to_string().bold(),
+            "#".repeat(count / (max / 80 + 1)).dimmed().bold(),
+            count,
+            format!("({:.1}%)", count as f64 * 100.0 / total.max(1) as f64).bright_black()
+        ));
+    }
+
     pub fn display_count_and_total(item: &str, count: usize, total: usize) {
         View::typewriter_for_line(&format!(
             "- {:<50} {:<6}{}",
@@ -220,6 +239,23 @@ impl View {
         ));
     }
 
+    pub fn display_item<T: ToString>(item: &str, value: T) {
+        View::typewriter_for_line(&format!(
+            "- {:<50} {}",
+            item.green().bold(),
+            value.to_string()
+        ));
+    }
+
+    pub fn format_duration(duration: Duration) -> String {
+        let secs = duration.as_secs();
+        match secs {
+            0..=59 => format!("{}s", secs),
+            60..=3599 => format!("{}m {}s", secs / 60, secs % 60),
+            _ => format!("{}h {}m", secs / 3600, secs % 3600 / 60),
+        }
+    }
+
     pub fn hint_finish(year: i32) {
         Self::sub_title(&format!("All {} command line stats wrapped!", year));
         Self::typewriter_for_line(&format!(
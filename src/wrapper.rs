@@ -0,0 +1,169 @@
+use serde::Deserialize;
+use std::collections::HashMap;
+
+const SUDO_OPTIONS: [&str; 20] = [
+    "-u",
+    "-g",
+    "-C",
+    "-D",
+    "-p",
+    "-r",
+    "-t",
+    "-T",
+    "-U",
+    "-R",
+    "--user",
+    "--group",
+    "--close-from",
+    "--chdir",
+    "--prompt",
+    "--role",
+    "--type",
+    "--command-timeout",
+    "--other-user",
+    "--chroot",
+];
+const XARGS_OPTIONS: [&str; 14] = [
+    "-a",
+    "--arg-file",
+    "-d",
+    "--delimiter",
+    "-E",
+    "-I",
+    "-L",
+    "--max-lines",
+    "-n",
+    "--max-args",
+    "-P",
+    "--max-procs",
+    "-s",
+    "--max-chars",
+];
+
+/// A precommand modifier or a program that runs the command given in its
+/// arguments, such as `sudo`, `nice -n 10` or `xargs`.
+///
+/// ```toml
+/// [wrappers.proxychains]
+/// options = ["-f"]
+/// ```
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Wrapper {
+    /// Options that take the next argument as their value, as the `-u` of
+    /// `sudo -u deploy`. Other arguments starting with `-` are read as flags.
+    #[serde(default)]
+    pub options: Vec<String>,
+    /// Operands between the options and the command, as the duration of
+    /// `timeout 5`.
+    #[serde(default)]
+    pub operands: usize,
+    /// Options that make the wrapper the command itself, as the `-v` of
+    /// `command -v ls`, which looks `ls` up without running it.
+    #[serde(default)]
+    pub standalone: Vec<String>,
+}
+
+impl Wrapper {
+    fn new(options: &[&str], operands: usize) -> Self {
+        Wrapper {
+            options: options.iter().map(|option| option.to_string()).collect(),
+            operands,
+            standalone: vec![],
+        }
+    }
+
+    fn standalone(mut self, options: &[&str]) -> Self {
+        self.standalone = options.iter().map(|option| option.to_string()).collect();
+        self
+    }
+}
+
+/// The table of wrappers skipped to find the program a command runs, the
+/// built-in ones and those declared in the config file.
+#[derive(Debug, Clone)]
+pub struct Wrappers(HashMap<String, Wrapper>);
+
+impl Default for Wrappers {
+    fn default() -> Self {
+        let wrappers = [
+            ("sudo", Wrapper::new(&SUDO_OPTIONS, 0)),
+            ("doas", Wrapper::new(&["-u", "-C"], 0)),
+            ("env", Wrapper::new(&["-u", "--unset", "-C", "--chdir"], 0)),
+            (
+                "time",
+                Wrapper::new(&["-f", "--format", "-o", "--output"], 0),
+            ),
+            ("nohup", Wrapper::new(&[], 0)),
+            ("nice", Wrapper::new(&["-n", "--adjustment"], 0)),
+            (
+                "timeout",
+                Wrapper::new(&["-s", "--signal", "-k", "--kill-after"], 1),
+            ),
+            ("exec", Wrapper::new(&["-a"], 0)),
+            ("command", Wrapper::new(&[], 0).standalone(&["-v", "-V"])),
+            ("builtin", Wrapper::new(&[], 0)),
+            ("noglob", Wrapper::new(&[], 0)),
+            ("nocorrect", Wrapper::new(&[], 0)),
+            ("xargs", Wrapper::new(&XARGS_OPTIONS, 0)),
+            ("watch", Wrapper::new(&["-n", "--interval"], 0)),
+        ];
+        Wrappers(
+            wrappers
+                .into_iter()
+                .map(|(name, wrapper)| (name.to_string(), wrapper))
+                .collect(),
+        )
+    }
+}
+
+impl Wrappers {
+    /// Adds a wrapper, replacing the built-in one of the same name.
+    pub fn insert(&mut self, name: String, wrapper: Wrapper) {
+        self.0.insert(name, wrapper);
+    }
+
+    /// Skips the wrappers and variable assignments at the start of an argument
+    /// vector. Returns the names of the wrappers, outermost first, and the
+    /// index of the command they run. A wrapper given no command, as in
+    /// `sudo -i`, is the command itself.
+    pub fn unwrap(&self, args: &[String]) -> (Vec<String>, Option<usize>) {
+        let mut wrappers = vec![];
+        let mut index = Self::skip_assignments(args, 0);
+        while let Some(i) = index {
+            // `/usr/bin/time` is `time` too
+            let name = args[i].rsplit('/').next().unwrap_or_default();
+            let Some(wrapper) = self.0.get(name) else {
+                break;
+            };
+            let mut next = i + 1;
+            while let Some(arg) = args.get(next) {
+                if wrapper.standalone.contains(arg) {
+                    return (wrappers, Some(i));
+                }
+                if arg == "--" {
+                    next += 1;
+                    break;
+                }
+                if !arg.starts_with('-') || arg == "-" {
+                    break;
+                }
+                next += if wrapper.options.contains(arg) { 2 } else { 1 };
+            }
+            match Self::skip_assignments(args, next + wrapper.operands) {
+                Some(command) => {
+                    wrappers.push(name.to_string());
+                    index = Some(command);
+                }
+                None => break,
+            }
+        }
+        (wrappers, index)
+    }
+
+    // assignments come before the command, as in `FOO=1 make` or `env FOO=1 make`
+    fn skip_assignments(args: &[String], from: usize) -> Option<usize> {
+        (from..args.len())
+            .find(|&i| !args[i].is_empty() && !args[i].contains('=') && !args[i].contains('{'))
+    }
+}